@@ -0,0 +1,90 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+/// `tokio::time::interval` panics on a zero period, so sweeps are at least this far apart.
+const MIN_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Server-wide settings, read from the environment on startup.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Lobbies without any activity for this long are closed.
+    pub idle_lobby_ttl: Duration,
+
+    /// Completed lobbies without any activity for this long are closed.
+    pub completed_lobby_ttl: Duration,
+
+    /// How often abandoned lobbies are looked for.
+    pub sweep_interval: Duration,
+
+    /// How long a lobby waits for its disconnected host to come back.
+    pub host_grace_period: Duration,
+
+    /// How long a disconnected player keeps their seat.
+    pub player_grace_period: Duration,
+
+    /// Where lobbies are saved to survive restarts. Lobbies only live in memory if unset.
+    pub data_dir: Option<PathBuf>,
+
+    /// The most players any lobby seats, whatever its host asks for.
+    pub max_players: u16,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self::from_vars(|key| env::var_os(key))
+    }
+
+    /// Reads the settings through `var`, which looks a variable up by name.
+    fn from_vars(var: impl Fn(&str) -> Option<OsString>) -> Self {
+        let seconds = |key, default| Duration::from_secs(parse(var(key)).unwrap_or(default));
+
+        Self {
+            idle_lobby_ttl: seconds("WFB_IDLE_LOBBY_TTL_SECS", 30 * 60),
+            completed_lobby_ttl: seconds("WFB_COMPLETED_LOBBY_TTL_SECS", 10 * 60),
+            sweep_interval: seconds("WFB_SWEEP_INTERVAL_SECS", 60).max(MIN_SWEEP_INTERVAL),
+            host_grace_period: seconds("WFB_HOST_GRACE_PERIOD_SECS", 120),
+            player_grace_period: seconds("WFB_PLAYER_GRACE_PERIOD_SECS", 120),
+            data_dir: var("WFB_DATA_DIR").map(PathBuf::from),
+            max_players: parse(var("WFB_MAX_PLAYERS")).unwrap_or(100),
+        }
+    }
+}
+
+fn parse<T: FromStr>(value: Option<OsString>) -> Option<T> {
+    value?.to_str()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn config_from(vars: &[(&str, &str)]) -> Config {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        Config::from_vars(|key| vars.get(key).map(OsString::from))
+    }
+
+    #[test]
+    fn sweep_interval_is_never_zero() {
+        assert_eq!(config_from(&[]).sweep_interval, Duration::from_secs(60));
+        assert_eq!(
+            config_from(&[("WFB_SWEEP_INTERVAL_SECS", "5")]).sweep_interval,
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            config_from(&[("WFB_SWEEP_INTERVAL_SECS", "0")]).sweep_interval,
+            MIN_SWEEP_INTERVAL
+        );
+        // Unparsable values fall back to the default
+        assert_eq!(
+            config_from(&[("WFB_SWEEP_INTERVAL_SECS", "-1")]).sweep_interval,
+            Duration::from_secs(60)
+        );
+    }
+}
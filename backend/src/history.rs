@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use socketioxide::socket::Sid;
+
+use crate::model::{
+    Card,
+    CardId,
+    Host,
+    Lobby,
+    LobbySettings,
+    LobbyState,
+    Placement,
+    Player,
+};
+
+/// Something that happened in a lobby. Together they are enough to rebuild the lobby.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum GameEvent {
+    #[serde(rename_all = "camelCase")]
+    Created {
+        host: String,
+        settings: LobbySettings,
+        cards: Vec<Card>,
+    },
+    Joined {
+        name: String,
+    },
+    Left {
+        name: String,
+    },
+    Kicked {
+        name: String,
+        banned: bool,
+    },
+    TeamChanged {
+        name: String,
+        team: Option<String>,
+    },
+    BoardSubmitted {
+        name: String,
+        cards: Vec<CardId>,
+    },
+
+    /// The server handed out a board, see [`Lobby::assign_random_boards`].
+    BoardAssigned {
+        name: String,
+        cards: Vec<CardId>,
+    },
+    StageAdvanced {
+        state: LobbyState,
+    },
+    #[serde(rename_all = "camelCase")]
+    AnswerSubmitted {
+        card_id: CardId,
+    },
+    #[serde(rename_all = "camelCase")]
+    AnswerRetracted {
+        card_id: CardId,
+    },
+
+    /// Only informational, replaying the answers awards the same places.
+    WinnerDetected {
+        placements: Vec<Placement>,
+    },
+    NewRound {
+        cards: Vec<Card>,
+    },
+    #[serde(rename_all = "camelCase")]
+    HostTransferred {
+        name: String,
+        keep_previous: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub at: DateTime<Utc>,
+
+    /// Who caused the event, `None` for the server itself.
+    pub actor: Option<String>,
+
+    #[serde(flatten)]
+    pub event: GameEvent,
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("The history can't be replayed past step {step}.")]
+pub struct ReplayError {
+    pub step: usize,
+}
+
+/// The append-only record of everything that happened in a lobby.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct History(Vec<HistoryEntry>);
+
+impl History {
+    pub fn record(&mut self, actor: Option<&str>, event: GameEvent) {
+        self.0.push(HistoryEntry {
+            at: Utc::now(),
+            actor: actor.map(str::to_string),
+            event,
+        });
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.0
+    }
+
+    /// Rebuilds the lobby as it was after the first `steps` entries. Sockets are made up, so only
+    /// names can be compared with the original lobby.
+    pub fn replay(&self, steps: usize) -> Result<Lobby, ReplayError> {
+        let entries = &self.0[..steps.min(self.0.len())];
+
+        let Some(HistoryEntry {
+            event:
+                GameEvent::Created {
+                    host,
+                    settings,
+                    cards,
+                },
+            ..
+        }) = entries.first()
+        else {
+            return Err(ReplayError { step: 0 });
+        };
+
+        let mut sids = HashMap::from([(host.clone(), Sid::new())]);
+        let mut lobby = Lobby::new(
+            Host::new(sids[host], host.clone()),
+            *settings,
+            cards.clone(),
+        );
+
+        for (step, entry) in entries.iter().enumerate().skip(1) {
+            let error = ReplayError { step };
+
+            match &entry.event {
+                GameEvent::Created { .. } => return Err(error),
+                GameEvent::Joined { name } => {
+                    let sid = Sid::new();
+                    sids.insert(name.clone(), sid);
+                    lobby.add_player(Player::new(sid, name.clone()));
+                }
+                GameEvent::Left { name } => {
+                    let sid = sids.get(name).ok_or(error)?;
+                    lobby.remove_player(sid);
+                }
+                GameEvent::Kicked { name, banned } => {
+                    let sid = sids.get(name).ok_or(error)?;
+                    lobby.kick_player(sid, *banned).ok_or(error)?;
+                }
+                GameEvent::TeamChanged { name, team } => {
+                    let sid = *sids.get(name).ok_or(error)?;
+                    lobby.set_team(sid, team.as_deref()).map_err(|_| error)?;
+                }
+                GameEvent::BoardSubmitted { name, cards } => {
+                    let sid = *sids.get(name).ok_or(error)?;
+                    lobby.submit_board(sid, cards.clone()).map_err(|_| error)?;
+                }
+                GameEvent::BoardAssigned { name, cards } => {
+                    let sid = *sids.get(name).ok_or(error)?;
+                    lobby.boards.insert(sid, cards.clone());
+                }
+                GameEvent::StageAdvanced { state } => {
+                    if lobby.advance_state().ok() != Some(*state) {
+                        return Err(error);
+                    }
+                }
+                GameEvent::AnswerSubmitted { card_id } => {
+                    lobby.submit_answer(*card_id).map_err(|_| error)?;
+                    lobby.record_placements();
+                }
+                GameEvent::AnswerRetracted { card_id } => {
+                    lobby.retract_answer(*card_id).map_err(|_| error)?;
+                }
+                GameEvent::WinnerDetected { .. } => {}
+                GameEvent::NewRound { cards } => {
+                    lobby
+                        .start_new_round(Some(cards.clone()))
+                        .map_err(|_| error)?;
+                }
+                GameEvent::HostTransferred {
+                    name,
+                    keep_previous,
+                } => {
+                    let sid = *sids.get(name).ok_or(error)?;
+                    lobby
+                        .transfer_host(sid, *keep_previous)
+                        .map_err(|_| error)?;
+                }
+            }
+        }
+
+        // Replaying doesn't record anything on its own
+        lobby.history = History(entries.to_vec());
+        Ok(lobby)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use socketioxide::socket::Sid;
+
+    use crate::{
+        history::{
+            GameEvent,
+            History,
+        },
+        model::{
+            Card,
+            Host,
+            Lobby,
+            LobbySettings,
+            LobbyState,
+            Player,
+        },
+    };
+
+    #[test]
+    fn replay_rebuilds_the_lobby_at_every_step() {
+        let settings = LobbySettings::default();
+        let pool: Vec<Card> = (0..25)
+            .map(|idx| Card::new(format!("card {idx}"), idx))
+            .collect();
+        let board: Vec<u16> = (0..25).collect();
+
+        let mut lobby = Lobby::new(Host::new(Sid::new(), "host".into()), settings, pool);
+        let player = Sid::new();
+        lobby.add_player(Player::new(player, "player".into()));
+        lobby.history.record(
+            Some("player"),
+            GameEvent::Joined {
+                name: "player".into(),
+            },
+        );
+
+        lobby.advance_state().unwrap();
+        lobby.history.record(
+            Some("host"),
+            GameEvent::StageAdvanced {
+                state: LobbyState::CraftingBoards,
+            },
+        );
+
+        lobby.submit_board(player, board.clone()).unwrap();
+        lobby.history.record(
+            Some("player"),
+            GameEvent::BoardSubmitted {
+                name: "player".into(),
+                cards: board,
+            },
+        );
+
+        lobby.advance_state().unwrap();
+        lobby.history.record(
+            Some("host"),
+            GameEvent::StageAdvanced {
+                state: LobbyState::InProgress,
+            },
+        );
+
+        for card_id in 0..5 {
+            lobby.submit_answer(card_id).unwrap();
+            lobby.record_placements();
+            lobby
+                .history
+                .record(Some("host"), GameEvent::AnswerSubmitted { card_id });
+        }
+
+        let history: &History = &lobby.history;
+        let replayed = history.replay(history.entries().len()).unwrap();
+        assert_eq!(replayed.state, LobbyState::InProgress);
+        assert_eq!(replayed.correct_answers, lobby.correct_answers);
+        assert_eq!(replayed.placements.len(), 1);
+        assert_eq!(replayed.placements[0].winner.name, "player");
+        assert_eq!(&replayed.history, history);
+
+        let before_answers = history.replay(5).unwrap();
+        assert_eq!(before_answers.state, LobbyState::InProgress);
+        assert!(before_answers.correct_answers.is_empty());
+        assert!(before_answers.placements.is_empty());
+
+        assert!(History::default().replay(1).is_err());
+    }
+}
@@ -1,9 +1,12 @@
 // src/main.rs
 
+pub mod config;
+pub mod history;
 pub mod model;
-pub mod request;
-pub mod response;
 pub mod socket;
+pub mod store;
+
+use std::sync::Arc;
 
 use axum::{
     Extension,
@@ -15,8 +18,13 @@ use tower_http::cors::CorsLayer;
 use tracing::info;
 
 use crate::{
+    config::Config,
     model::LobbyManager,
-    socket::on_connect,
+    socket::{
+        on_connect,
+        sweeper,
+    },
+    store::FileStore,
 };
 
 #[tokio::main]
@@ -24,20 +32,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Set up logging
     tracing_subscriber::fmt::init();
 
-    let lobby_manager = LobbyManager::new();
+    let config = Config::from_env();
+
+    let lobby_manager = match &config.data_dir {
+        Some(dir) => LobbyManager::load(Arc::new(FileStore::new(dir)?))?,
+        None => LobbyManager::new(),
+    };
 
     let (layer, io) = SocketIo::builder()
         .with_state(lobby_manager.clone())
+        .with_state(config.clone())
         .build_layer();
 
     io.ns("/", on_connect);
 
+    sweeper::resume_loaded_lobbies(&io, &lobby_manager, &config).await;
+
+    tokio::spawn(sweeper::run(io.clone(), lobby_manager.clone(), config));
+
     // Create the Axum application
     let app = Router::new()
         .with_state(lobby_manager)
         .layer(
             ServiceBuilder::new()
-                .layer(CorsLayer::very_permissive()) // Allow all origins for development
+                // Allow all origins for development, with credentials so the handshake carries the
+                // resume cookies
+                .layer(CorsLayer::very_permissive())
                 .layer(layer),
         )
         .layer(Extension(io));
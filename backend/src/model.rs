@@ -1,9 +1,16 @@
 use std::{
-    collections::HashMap,
+    cmp::Ordering,
+    collections::{
+        HashMap,
+        HashSet,
+        VecDeque,
+    },
     fmt::{
         self,
         Display,
     },
+    io,
+    ops::Deref,
     str::FromStr,
     sync::Arc,
 };
@@ -12,7 +19,12 @@ use chrono::{
     DateTime,
     Utc,
 };
-use rand::Rng;
+use rand::{
+    Rng,
+    SeedableRng,
+    seq::index,
+};
+use rand_chacha::ChaCha8Rng;
 use serde::{
     Deserialize,
     Deserializer,
@@ -24,11 +36,38 @@ use serde_repr::{
     Serialize_repr,
 };
 use socketioxide::socket::Sid;
-use tokio::sync::Mutex;
+use tokio::sync::{
+    Mutex,
+    MutexGuard,
+};
+
+use crate::{
+    history::{
+        GameEvent,
+        History,
+    },
+    store::{
+        LobbyStore,
+        StoreWriter,
+    },
+};
 
 // A character set for a base62 encoding.
 const CHARSET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
 const ID_LENGTH: usize = 6;
+const TOKEN_LENGTH: usize = 32;
+
+/// The largest card pool a host can supply.
+pub const MAX_CARD_POOL: usize = 200;
+
+/// The longest stage deadline a host can set, in seconds.
+pub const MAX_STAGE_SECONDS: u32 = 24 * 60 * 60;
+
+/// The longest team name, in characters.
+pub const MAX_TEAM_NAME_LENGTH: usize = 32;
+
+/// How many past events a lobby keeps around for clients that missed them.
+pub const MAX_LOGGED_EVENTS: usize = 256;
 
 // Function to generate a random ID of a given length.
 fn generate_short_id() -> [char; ID_LENGTH] {
@@ -53,6 +92,12 @@ impl LobbyId {
     }
 }
 
+impl Default for LobbyId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FromStr for LobbyId {
     type Err = String;
 
@@ -100,49 +145,241 @@ impl Serialize for LobbyId {
     }
 }
 
+/// A secret that lets a client take back its seat after reconnecting with a new socket. Kept out
+/// of logs like [`Secret`].
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ResumeToken(String);
+
+impl ResumeToken {
+    pub fn new() -> Self {
+        let mut rng = rand::rng();
+        let token = (0..TOKEN_LENGTH)
+            .map(|_| CHARSET[rng.random_range(0..CHARSET.len())] as char)
+            .collect();
+
+        Self(token)
+    }
+}
+
+impl fmt::Debug for ResumeToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ResumeToken(..)")
+    }
+}
+
+impl From<&str> for ResumeToken {
+    fn from(token: &str) -> Self {
+        Self(token.to_string())
+    }
+}
+
+impl Default for ResumeToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A lobby password or invite secret. Kept out of logs and only compared in constant time.
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    /// A random invite secret.
+    pub fn generate() -> Self {
+        Self(ResumeToken::new().0)
+    }
+
+    /// Compares without returning early, so the time taken doesn't tell how much of `candidate`
+    /// was right.
+    pub fn matches(&self, candidate: &Secret) -> bool {
+        let (expected, candidate) = (self.0.as_bytes(), candidate.0.as_bytes());
+        if expected.len() != candidate.len() {
+            return false;
+        }
+
+        let difference = expected
+            .iter()
+            .zip(candidate)
+            .fold(0, |difference, (a, b)| difference | (a ^ b));
+        std::hint::black_box(difference) == 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
+/// The index of a card in the lobby's card pool.
+pub type CardId = u16;
+
+/// Reproduces a generated board. Kept at 32 bits so it survives JavaScript numbers.
+pub type BoardSeed = u32;
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[serde(rename_all = "camelCase")]
 pub struct Card {
-    id: u8,
+    id: CardId,
     description: String,
 }
 
 impl Card {
-    pub fn new(description: String, idx: u8) -> Self {
+    pub fn new(description: String, idx: CardId) -> Self {
         Self {
             description,
             id: idx,
         }
     }
+
+    pub fn id(&self) -> CardId {
+        self.id
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct LobbyManager {
-    pub lobbies: Arc<Mutex<HashMap<LobbyId, Lobby>>>,
+    lobbies: Arc<Mutex<HashMap<LobbyId, Lobby>>>,
+    writer: Option<StoreWriter>,
+}
+
+impl fmt::Debug for LobbyManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LobbyManager").finish_non_exhaustive()
+    }
 }
 
 impl LobbyManager {
     pub fn new() -> Self {
         Self {
             lobbies: Arc::new(Mutex::new(HashMap::new())),
+            writer: None,
+        }
+    }
+
+    /// Starts out with every lobby saved in `store` and keeps saving them there.
+    pub fn load(store: Arc<dyn LobbyStore>) -> io::Result<Self> {
+        Ok(Self {
+            lobbies: Arc::new(Mutex::new(store.load()?)),
+            writer: Some(StoreWriter::spawn(store)?),
+        })
+    }
+
+    pub async fn lock(&self) -> Lobbies<'_> {
+        Lobbies {
+            lobbies: self.lobbies.lock().await,
+            writer: self.writer.as_ref(),
+            touched: HashSet::new(),
         }
     }
 
-    pub async fn create_lobby(&self, lobby_id: LobbyId, host: Host, cards: [Card; 25]) {
-        let mut lock = self.lobbies.lock().await;
-        lock.insert(lobby_id, Lobby::new(host, cards));
+    /// Adds the lobby and returns its host's resume token.
+    pub async fn create_lobby(&self, lobby_id: LobbyId, lobby: Lobby) -> ResumeToken {
+        let token = lobby.host_token.clone();
+
+        let mut lock = self.lock().await;
+        lock.insert(lobby_id, lobby);
+        token
     }
 
     pub async fn remove_lobby(&self, lobby_id: &LobbyId) -> Option<Lobby> {
-        let mut lock = self.lobbies.lock().await;
+        let mut lock = self.lock().await;
         lock.remove(lobby_id)
     }
 }
 
+/// Locked access to every lobby. On drop, lobbies marked with [`Lobby::mark_changed`] or that
+/// emitted new events are handed to the store, as are removed lobbies.
+pub struct Lobbies<'a> {
+    lobbies: MutexGuard<'a, HashMap<LobbyId, Lobby>>,
+    writer: Option<&'a StoreWriter>,
+    touched: HashSet<LobbyId>,
+}
+
+impl Lobbies<'_> {
+    pub fn get_mut(&mut self, lobby_id: &LobbyId) -> Option<&mut Lobby> {
+        let lobby = self.lobbies.get_mut(lobby_id)?;
+        self.touched.insert(*lobby_id);
+        Some(lobby)
+    }
+
+    pub fn insert(&mut self, lobby_id: LobbyId, mut lobby: Lobby) -> Option<Lobby> {
+        lobby.mark_changed();
+        self.touched.insert(lobby_id);
+        self.lobbies.insert(lobby_id, lobby)
+    }
+
+    pub fn remove(&mut self, lobby_id: &LobbyId) -> Option<Lobby> {
+        let lobby = self.lobbies.remove(lobby_id)?;
+        self.touched.insert(*lobby_id);
+        Some(lobby)
+    }
+}
+
+impl Deref for Lobbies<'_> {
+    type Target = HashMap<LobbyId, Lobby>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.lobbies
+    }
+}
+
+impl Drop for Lobbies<'_> {
+    fn drop(&mut self) {
+        let Some(writer) = self.writer else {
+            return;
+        };
+
+        for lobby_id in self.touched.drain() {
+            match self.lobbies.get_mut(&lobby_id) {
+                Some(lobby) => {
+                    if lobby.take_unsaved() {
+                        writer.save(lobby_id, lobby.clone());
+                    }
+                }
+                None => writer.remove(lobby_id),
+            }
+        }
+    }
+}
+
+impl Default for LobbyManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("The lobby has already reached the last stage.")]
 pub struct LastStateReached;
 
+#[derive(Debug, thiserror::Error)]
+#[error("The resume token is not valid for this lobby.")]
+pub struct InvalidResumeToken;
+
+#[derive(Debug, thiserror::Error)]
+#[error("A new round can only be started once the game is completed.")]
+pub struct RoundNotCompleted;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Expected between {min} and {MAX_CARD_POOL} cards, got {got}.")]
+pub struct InvalidCardPool {
+    pub min: usize,
+    pub got: usize,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
 #[repr(u8)]
 pub enum LobbyState {
@@ -172,171 +409,2661 @@ impl LobbyState {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Lobby {
-    pub host: Host,
-    pub available_cards: [Card; 25],
-    pub players: HashMap<Sid, Player>,
-    pub start_date: DateTime<Utc>,
-    pub state: LobbyState,
-
-    // We only save the card ids. They are enough for checking the winner.
-    pub correct_answers: Vec<u8>,
-    pub boards: HashMap<Sid, [u8; 25]>,
+/// The side length of a (square) bingo board.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum BoardSize {
+    Three = 3,
+    Four = 4,
+    #[default]
+    Five = 5,
+    Six = 6,
+    Seven = 7,
 }
 
-impl Lobby {
-    pub fn new(host: Host, available_cards: [Card; 25]) -> Self {
-        Self {
-            host,
-            available_cards,
-            players: HashMap::new(),
-            correct_answers: Vec::with_capacity(25),
-            start_date: Utc::now(),
-            state: LobbyState::WaitingForPlayers,
-            boards: HashMap::new(),
-        }
+impl BoardSize {
+    /// The number of cells in a row or column.
+    pub fn side(self) -> usize {
+        self as usize
     }
 
-    pub fn is_host(&self, socket_id: Sid) -> bool {
-        self.host.id == socket_id
+    /// The total number of cells on the board.
+    pub fn cell_count(self) -> usize {
+        self.side() * self.side()
     }
 
-    pub fn remove_player(&mut self, sid: &Sid) -> Option<Player> {
-        self.boards.remove(sid);
-        self.players.remove(sid)
-    }
+    /// Every row, column and both diagonals as lists of cell indices.
+    pub fn lines(self) -> Vec<Vec<usize>> {
+        let side = self.side();
 
-    pub fn advance_state(&mut self) -> Result<LobbyState, LastStateReached> {
-        self.state = self.state.next_stage().ok_or(LastStateReached)?;
-        Ok(self.state)
+        let rows = (0..side).map(|row| (0..side).map(|col| row * side + col).collect());
+        let columns = (0..side).map(|col| (0..side).map(|row| row * side + col).collect());
+        let main_diagonal = (0..side).map(|i| i * side + i).collect();
+        let other_diagonal = (0..side).map(|i| i * side + (side - 1 - i)).collect();
+
+        rows.chain(columns)
+            .chain([main_diagonal, other_diagonal])
+            .collect()
     }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidWinPattern {
+    #[error("The plus pattern needs a board with a center row and column.")]
+    NoCenter,
 
-    pub fn check_winners(&self) -> Vec<&str> {
-        let mut winners = Vec::new();
+    #[error("The custom pattern does not mark any cell.")]
+    EmptyMask,
 
-        for (player_id, board) in &self.boards {
-            if check_winner_board(*board, &self.correct_answers)
-                && let Some(player) = self.players.get(player_id)
-            {
-                winners.push(player.name.as_str());
+    #[error("The custom pattern marks cells outside of the board.")]
+    MaskOutOfBounds,
+}
+
+/// The condition a board has to fulfill to win.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum WinPattern {
+    /// Any complete row, column or diagonal
+    #[default]
+    AnyLine,
+
+    /// Two distinct complete rows, columns or diagonals
+    TwoLines,
+
+    /// The four corner cells
+    FourCorners,
+
+    /// Both diagonals
+    XShape,
+
+    /// The center row and the center column
+    Plus,
+
+    /// Every cell on the edge of the board
+    Frame,
+
+    /// Every cell of the board
+    Blackout,
+
+    /// Every cell whose bit is set, counted row by row from the top-left cell
+    Custom { mask: u64 },
+}
+
+impl WinPattern {
+    pub fn validate(self, size: BoardSize) -> Result<(), InvalidWinPattern> {
+        match self {
+            WinPattern::Plus if size.side().is_multiple_of(2) => Err(InvalidWinPattern::NoCenter),
+            WinPattern::Custom { mask: 0 } => Err(InvalidWinPattern::EmptyMask),
+            WinPattern::Custom { mask } if mask >> size.cell_count() != 0 => {
+                Err(InvalidWinPattern::MaskOutOfBounds)
             }
+            _ => Ok(()),
         }
+    }
 
-        winners
+    /// The cells a shape pattern requires, or `None` for the line based patterns.
+    fn shape(self, size: BoardSize) -> Option<Vec<usize>> {
+        let side = size.side();
+        let cells = 0..size.cell_count();
+
+        let shape = match self {
+            WinPattern::AnyLine | WinPattern::TwoLines => return None,
+            WinPattern::FourCorners => vec![0, side - 1, (side - 1) * side, side * side - 1],
+            WinPattern::XShape => cells
+                .filter(|cell| {
+                    let (row, col) = (cell / side, cell % side);
+                    row == col || row + col == side - 1
+                })
+                .collect(),
+            WinPattern::Plus => cells
+                .filter(|cell| cell / side == side / 2 || cell % side == side / 2)
+                .collect(),
+            WinPattern::Frame => cells
+                .filter(|cell| {
+                    let (row, col) = (cell / side, cell % side);
+                    row == 0 || col == 0 || row == side - 1 || col == side - 1
+                })
+                .collect(),
+            WinPattern::Blackout => cells.collect(),
+            WinPattern::Custom { mask } => cells.filter(|cell| mask & (1 << cell) != 0).collect(),
+        };
+
+        Some(shape)
     }
 }
 
-fn check_winner_board(board: [u8; 25], correct_answers: &[u8]) -> bool {
-    // Check rows
-    for row in 0..5 {
-        let start = row * 5;
-        let row_slice = &board[start..start + 5];
-        if row_slice.iter().all(|id| correct_answers.contains(id)) {
-            return true;
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidSettings {
+    #[error(transparent)]
+    WinPattern(#[from] InvalidWinPattern),
+
+    #[error("At least one place has to be awarded.")]
+    NoPlaces,
+
+    #[error("A free center space needs a board with a center cell.")]
+    NoCenterCell,
+
+    #[error("Stage deadlines must be between 1 and {MAX_STAGE_SECONDS} seconds.")]
+    InvalidDeadline,
+
+    #[error("A lobby needs at least one seat.")]
+    NoSeats,
+}
+
+/// Optional time limits of the lobby's stages, in seconds. The lobby advances on its own once
+/// they pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StageDeadlines {
+    pub crafting: Option<u32>,
+    pub game: Option<u32>,
+}
+
+impl StageDeadlines {
+    pub fn seconds(&self, state: LobbyState) -> Option<u32> {
+        match state {
+            LobbyState::CraftingBoards => self.crafting,
+            LobbyState::InProgress => self.game,
+            LobbyState::WaitingForPlayers | LobbyState::Completed => None,
         }
     }
+}
 
-    // Check columns
-    for col in 0..5 {
-        if (0..5).all(|row| correct_answers.contains(&board[row * 5 + col])) {
-            return true;
+/// The options a host chooses when creating a lobby.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LobbySettings {
+    pub board_size: BoardSize,
+    pub win_pattern: WinPattern,
+
+    /// How many places are awarded before the game ends on its own.
+    pub places: u8,
+
+    /// Reserves the center cell as a free space that is always marked.
+    pub free_center: bool,
+
+    /// Who gets told about players that are one card away from winning.
+    pub near_wins: NearWinVisibility,
+
+    /// The server hands out random boards and the crafting stage is skipped.
+    pub auto_boards: bool,
+
+    /// Players form teams that share a board and win together.
+    pub teams: bool,
+
+    pub deadlines: StageDeadlines,
+
+    /// A player takes over when the host doesn't come back in time, instead of the lobby closing.
+    pub promote_host: bool,
+
+    /// Spectators see every player's board once the game is in progress.
+    pub spectator_boards: bool,
+
+    /// The most players the lobby seats, further ones can wait for a seat. Capped by the server.
+    pub max_players: Option<u16>,
+}
+
+impl Default for LobbySettings {
+    fn default() -> Self {
+        Self {
+            board_size: BoardSize::default(),
+            win_pattern: WinPattern::default(),
+            places: 1,
+            free_center: false,
+            near_wins: NearWinVisibility::default(),
+            auto_boards: false,
+            teams: false,
+            deadlines: StageDeadlines::default(),
+            promote_host: false,
+            spectator_boards: false,
+            max_players: None,
         }
     }
+}
+
+impl LobbySettings {
+    pub fn validate(&self) -> Result<(), InvalidSettings> {
+        self.win_pattern.validate(self.board_size)?;
+
+        if self.places == 0 {
+            return Err(InvalidSettings::NoPlaces);
+        }
+
+        if self.free_center && self.board_size.side().is_multiple_of(2) {
+            return Err(InvalidSettings::NoCenterCell);
+        }
+
+        if [self.deadlines.crafting, self.deadlines.game]
+            .into_iter()
+            .flatten()
+            .any(|seconds| !(1..=MAX_STAGE_SECONDS).contains(&seconds))
+        {
+            return Err(InvalidSettings::InvalidDeadline);
+        }
+
+        if self.max_players == Some(0) {
+            return Err(InvalidSettings::NoSeats);
+        }
 
-    // Check main diagonal (top-left to bottom-right)
-    if (0..5).all(|i| correct_answers.contains(&board[i * 5 + i])) {
-        return true;
+        Ok(())
     }
 
-    // Check other diagonal (top-right to bottom-left)
-    if (0..5).all(|i| correct_answers.contains(&board[i * 5 + (4 - i)])) {
-        return true;
+    /// Numbers the cards of a card pool, which has to hold enough cards to fill every cell except
+    /// a free center.
+    pub fn card_pool(&self, descriptions: Vec<String>) -> Result<Vec<Card>, InvalidCardPool> {
+        let min = self.cards_per_board();
+        if !(min..=MAX_CARD_POOL).contains(&descriptions.len()) {
+            return Err(InvalidCardPool {
+                min,
+                got: descriptions.len(),
+            });
+        }
+
+        Ok(descriptions
+            .into_iter()
+            .enumerate()
+            .map(|(idx, description)| Card::new(description, idx as CardId))
+            .collect())
     }
 
-    false
+    /// The number of real cards on a board, leaving out the free center space.
+    pub fn cards_per_board(&self) -> usize {
+        self.board_size.cell_count() - usize::from(self.free_center)
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Host {
-    pub name: String,
-    pub id: Sid,
+pub enum NearWinVisibility {
+    /// Nobody, near wins are not reported.
+    Hidden,
+
+    /// Only the host.
+    Host,
+
+    /// Everyone in the lobby.
+    #[default]
+    Everyone,
 }
 
-impl Host {
-    pub fn new(id: Sid, name: String) -> Self {
-        Self { id, name }
+impl NearWinVisibility {
+    pub fn visible_to(self, role: Role) -> bool {
+        match self {
+            NearWinVisibility::Hidden => false,
+            NearWinVisibility::Host => role == Role::Host,
+            NearWinVisibility::Everyone => true,
+        }
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+/// What a socket is to a lobby.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Player {
-    pub id: Sid,
+pub enum Role {
+    Host,
+    Player,
+
+    /// Watches the lobby without taking part in it.
+    Spectator,
+}
+
+/// The results of a player, or team, over every round played in a lobby.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Score {
     pub name: String,
+
+    /// The last place awarded in a round is worth one point, every place above one more.
+    pub points: u32,
+
+    /// Rounds finished in first place.
+    pub wins: u32,
 }
 
-impl Player {
-    pub fn new(id: Sid, name: String) -> Self {
-        Self { id, name }
+/// Why a lobby was closed by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CloseReason {
+    /// Nothing happened in the lobby for too long.
+    Idle,
+
+    /// The game ended a while ago.
+    Completed,
+
+    /// The host disconnected and didn't come back in time.
+    HostLeft,
+}
+
+impl Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloseReason::Idle => write!(f, "idle"),
+            CloseReason::Completed => write!(f, "completed"),
+            CloseReason::HostLeft => write!(f, "host left"),
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::model::check_winner_board;
+/// A random board picked from the card pool.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneratedBoard {
+    pub seed: BoardSeed,
+    pub cards: Vec<CardId>,
+}
 
-    #[rustfmt::skip]
-    const fn sample_board() -> [u8; 25] {
-        [
-        // Columns
-        //   0   1   2   3   4  // rows
-             1,  2,  3,  4,  5, // 0
-             6,  7,  8,  9, 10, // 1
-            11, 12, 13, 14, 15, // 2
-            16, 17, 18, 19, 20, // 3
-            21, 22, 23, 24, 25, // 4
-        ]
-    }
+/// A player who is a single card away from winning.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NearWin {
+    pub name: String,
 
-    #[test]
-    fn row_win_detected() {
-        let board = sample_board();
-        // row 1 (second row): 6,7,8,9,10 scrambled
-        let correct = vec![9, 6, 10, 7, 8];
-        assert!(check_winner_board(board, &correct));
-    }
+    /// Each of these cards would complete the win pattern on its own.
+    pub cards: Vec<CardId>,
+}
 
-    #[test]
-    fn column_win_detected() {
-        let board = sample_board();
-        // column 2 (third column): values 3,8,13,18,23 scrambled
-        let correct = vec![18, 3, 23, 8, 13];
-        assert!(check_winner_board(board, &correct));
-    }
+/// A player whose board fulfills the lobby's win pattern.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Winner {
+    /// The player's name, or the team's name in team mode.
+    pub name: String,
 
-    #[test]
-    fn main_diagonal_win_detected() {
-        let board = sample_board();
-        // main diagonal: values 1,7,13,19,25 scrambled
-        let correct = vec![13, 25, 1, 19, 7];
-        assert!(check_winner_board(board, &correct));
-    }
+    /// The team's members in team mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+    pub pattern: WinPattern,
 
-    #[test]
-    fn other_diagonal_win_detected() {
-        let board = sample_board();
-        // other diagonal: values 5,9,13,17,21 scrambled
-        let correct = vec![21, 13, 5, 17, 9];
-        assert!(check_winner_board(board, &correct));
-    }
+    /// The board cells that completed the pattern.
+    pub cells: Vec<usize>,
+}
 
-    #[test]
-    fn no_win_detected() {
-        let board = sample_board();
-        // scattered answers that don't make any full row/col/diagonal
-        let correct = vec![14, 1, 22, 6, 3]; // scrambled scattered set
-        assert!(!check_winner_board(board, &correct));
+/// A winner together with the place they finished in.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Placement {
+    /// 1-based place. Players winning on the same answer share a place.
+    pub place: usize,
+
+    #[serde(flatten)]
+    pub winner: Winner,
+
+    /// Index into the correct answers of the answer that completed the board.
+    pub answer_index: usize,
+    pub won_at: DateTime<Utc>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidBoard {
+    #[error("Expected a board of {expected} cards, got {got}.")]
+    WrongLength { expected: usize, got: usize },
+
+    #[error("The card {0} is not part of the lobby's card pool.")]
+    UnknownCard(CardId),
+
+    #[error("The card {0} is placed more than once.")]
+    DuplicateCard(CardId),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubmitBoardError {
+    #[error("Boards can only be submitted while crafting boards.")]
+    NotCrafting,
+
+    #[error("Only players can submit a board.")]
+    NotAPlayer,
+
+    #[error(transparent)]
+    InvalidBoard(#[from] InvalidBoard),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubmitAnswerError {
+    #[error("Answers can only be submitted while the game is in progress.")]
+    NotInProgress,
+
+    #[error("The card {0} is not part of the lobby's card pool.")]
+    UnknownCard(CardId),
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum RetractAnswerError {
+    #[error("Answers can only be retracted while the game is in progress or completed.")]
+    WrongStage,
+
+    #[error("The card was never submitted.")]
+    NotSubmitted,
+
+    #[error("A completed game only takes back answers that cost someone their place.")]
+    NoPlaceRevoked,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum JoinError {
+    #[error("You are already part of this lobby.")]
+    AlreadyInLobby,
+
+    #[error("The lobby is not accepting new players.")]
+    NotWaiting,
+
+    #[error("You are banned from this lobby.")]
+    Banned,
+
+    #[error("The player name is already taken.")]
+    NameTaken,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransferHostError {
+    #[error("Only players can become the host.")]
+    NotAPlayer,
+
+    #[error("The player is disconnected.")]
+    Disconnected,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TeamError {
+    #[error("The lobby is not in team mode.")]
+    TeamsDisabled,
+
+    #[error("Teams can only be changed while waiting for players.")]
+    NotWaiting,
+
+    #[error("Only players can be part of a team.")]
+    NotAPlayer,
+
+    #[error("Team names must be between 1 and {MAX_TEAM_NAME_LENGTH} characters long.")]
+    InvalidName,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Lobby {
+    pub host: Host,
+    pub settings: LobbySettings,
+    pub available_cards: Vec<Card>,
+    pub players: HashMap<Sid, Player>,
+    pub teams: Vec<Team>,
+    pub start_date: DateTime<Utc>,
+    pub state: LobbyState,
+
+    // We only save the card ids. They are enough for checking the winner.
+    pub correct_answers: Vec<CardId>,
+    pub boards: HashMap<Sid, Vec<CardId>>,
+    pub placements: Vec<Placement>,
+
+    /// 1-based number of the current round.
+    pub round: u32,
+
+    /// Cumulative results of every completed round.
+    pub scores: Vec<Score>,
+
+    /// When the current stage ends on its own.
+    pub deadline: Option<DateTime<Utc>>,
+
+    /// The last time anything changed, used to close abandoned lobbies.
+    pub last_activity: DateTime<Utc>,
+
+    /// Lets the host take the lobby back after reconnecting.
+    pub host_token: ResumeToken,
+
+    /// Set while the host is disconnected and may still come back.
+    pub host_disconnected_at: Option<DateTime<Utc>>,
+
+    /// The most recent events emitted to the lobby.
+    pub events: EventLog,
+
+    /// Everything that happened in the lobby, for replaying the game later.
+    pub history: History,
+
+    /// Sockets watching the lobby. Not saved, as their sockets don't survive a restart.
+    #[serde(skip)]
+    pub spectators: HashSet<Sid>,
+
+    /// Names that may not join the lobby again.
+    pub banned_names: HashSet<String>,
+
+    /// Resume tokens that may not take a seat back.
+    pub banned_tokens: HashSet<ResumeToken>,
+
+    /// Required to join or spectate, unless the invite secret is given instead.
+    pub password: Option<Secret>,
+
+    /// Shared by the host as an alternative to the password.
+    pub invite_secret: Option<Secret>,
+
+    /// Sockets waiting for a seat, in order. Not saved, like spectators.
+    #[serde(skip)]
+    pub waitlist: VecDeque<WaitlistEntry>,
+
+    /// Sockets of banned players, which may neither join under another name nor spectate. Not
+    /// saved, like spectators.
+    #[serde(skip)]
+    pub banned_sockets: HashSet<Sid>,
+
+    /// Set by [`Lobby::mark_changed`] until the lobby is handed to the store.
+    #[serde(skip)]
+    unsaved: bool,
+}
+
+/// A socket waiting for a seat in a full lobby.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitlistEntry {
+    pub id: Sid,
+    pub name: String,
+}
+
+impl Lobby {
+    pub fn new(host: Host, settings: LobbySettings, available_cards: Vec<Card>) -> Self {
+        let mut history = History::default();
+        history.record(
+            Some(&host.name),
+            GameEvent::Created {
+                host: host.name.clone(),
+                settings,
+                cards: available_cards.clone(),
+            },
+        );
+
+        Self {
+            host,
+            settings,
+            available_cards,
+            players: HashMap::new(),
+            teams: Vec::new(),
+            correct_answers: Vec::with_capacity(settings.board_size.cell_count()),
+            start_date: Utc::now(),
+            state: LobbyState::WaitingForPlayers,
+            boards: HashMap::new(),
+            placements: Vec::new(),
+            round: 1,
+            scores: Vec::new(),
+            deadline: None,
+            last_activity: Utc::now(),
+            host_token: ResumeToken::new(),
+            host_disconnected_at: None,
+            events: EventLog::default(),
+            history,
+            spectators: HashSet::new(),
+            banned_names: HashSet::new(),
+            banned_tokens: HashSet::new(),
+            password: None,
+            invite_secret: None,
+            waitlist: VecDeque::new(),
+            banned_sockets: HashSet::new(),
+            unsaved: false,
+        }
+    }
+
+    /// Has the lobby saved once the lock on it is released.
+    pub fn mark_changed(&mut self) {
+        self.unsaved = true;
+    }
+
+    /// Whether the lobby was marked as changed or numbered an event since the last call.
+    fn take_unsaved(&mut self) -> bool {
+        let unsaved = self.unsaved || self.events.unsaved;
+        self.unsaved = false;
+        self.events.unsaved = false;
+        unsaved
+    }
+
+    /// Starts the host's grace period and returns when it started.
+    pub fn disconnect_host(&mut self) -> DateTime<Utc> {
+        let now = Utc::now();
+        self.host_disconnected_at = Some(now);
+        now
+    }
+
+    /// Gives the lobby back to the host on their new socket `sid`.
+    pub fn resume_host(&mut self, sid: Sid, token: &ResumeToken) -> Result<(), InvalidResumeToken> {
+        if self.host_token != *token {
+            return Err(InvalidResumeToken);
+        }
+
+        self.host.id = sid;
+        self.host_disconnected_at = None;
+        self.touch();
+        Ok(())
+    }
+
+    pub fn touch(&mut self) {
+        self.last_activity = Utc::now();
+    }
+
+    /// Why the lobby should be closed at `now`, if it should be closed at all. A lobby waiting for
+    /// its stage deadline isn't idle, however long the stage takes.
+    pub fn expiry(
+        &self,
+        now: DateTime<Utc>,
+        idle_ttl: chrono::Duration,
+        completed_ttl: chrono::Duration,
+    ) -> Option<CloseReason> {
+        let inactive = now - self.last_activity;
+
+        if self.state == LobbyState::Completed && inactive >= completed_ttl {
+            Some(CloseReason::Completed)
+        } else if inactive >= idle_ttl && self.deadline.is_none_or(|deadline| deadline <= now) {
+            Some(CloseReason::Idle)
+        } else {
+            None
+        }
+    }
+
+    /// Adds the player, who stops spectating if they did so far.
+    pub fn add_player(&mut self, player: Player) {
+        self.spectators.remove(&player.id);
+        self.players.insert(player.id, player);
+        self.touch();
+    }
+
+    pub fn is_host(&self, socket_id: Sid) -> bool {
+        self.host.id == socket_id
+    }
+
+    pub fn remove_player(&mut self, sid: &Sid) -> Option<Player> {
+        self.boards.remove(sid);
+        self.leave_team(sid);
+        self.touch();
+        self.players.remove(sid)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.settings
+            .max_players
+            .is_some_and(|max| self.players.len() >= usize::from(max))
+    }
+
+    /// Whether the host, a player, or someone on the waitlist goes by `name`.
+    pub fn name_taken(&self, name: &str) -> bool {
+        self.host.name == name
+            || self.players.values().any(|player| player.name == name)
+            || self.waitlist.iter().any(|entry| entry.name == name)
+    }
+
+    /// Checks whether the socket `sid` may take a seat, or wait for one, as `name`. Spectators may,
+    /// while the host and sockets already seated or waiting may not.
+    pub fn check_join(&self, sid: Sid, name: &str) -> Result<(), JoinError> {
+        if self.is_host(sid)
+            || self.players.contains_key(&sid)
+            || self.waitlist.iter().any(|entry| entry.id == sid)
+        {
+            return Err(JoinError::AlreadyInLobby);
+        }
+
+        if self.state != LobbyState::WaitingForPlayers {
+            return Err(JoinError::NotWaiting);
+        }
+
+        if self.banned_names.contains(name) || self.banned_sockets.contains(&sid) {
+            return Err(JoinError::Banned);
+        }
+
+        if self.name_taken(name) {
+            return Err(JoinError::NameTaken);
+        }
+
+        Ok(())
+    }
+
+    pub fn player_id_by_name(&self, name: &str) -> Option<Sid> {
+        self.players
+            .values()
+            .find(|player| player.name == name)
+            .map(|player| player.id)
+    }
+
+    /// Puts the socket `sid` at the end of the waitlist and returns its 1-based position.
+    pub fn join_waitlist(&mut self, sid: Sid, name: String) -> usize {
+        self.waitlist.push_back(WaitlistEntry { id: sid, name });
+        self.waitlist.len()
+    }
+
+    /// Takes the socket `sid` off the waitlist. Returns whether it was waiting.
+    pub fn leave_waitlist(&mut self, sid: &Sid) -> bool {
+        let waiting = self.waitlist.len();
+        self.waitlist.retain(|entry| entry.id != *sid);
+        self.waitlist.len() != waiting
+    }
+
+    /// Whether the password or the invite secret lets a socket in. Open lobbies let everyone in.
+    pub fn admits(&self, password: Option<&Secret>, invite: Option<&Secret>) -> bool {
+        if self.password.is_none() && self.invite_secret.is_none() {
+            return true;
+        }
+
+        // Unset secrets aren't compared, so the time taken may tell which ones the lobby has. Only
+        // their contents are kept from leaking.
+        secret_matches(&self.password, password) || secret_matches(&self.invite_secret, invite)
+    }
+
+    /// Removes the player `sid`, keeping them from coming back if `ban` is set.
+    pub fn kick_player(&mut self, sid: &Sid, ban: bool) -> Option<Player> {
+        let player = self.remove_player(sid)?;
+
+        if ban {
+            self.banned_sockets.insert(*sid);
+            self.banned_names.insert(player.name.clone());
+            self.banned_tokens.insert(player.token.clone());
+        }
+
+        Some(player)
+    }
+
+    /// Starts the grace period of the host and every player, as none of their sockets survived a
+    /// restart. Returns when it started.
+    pub fn disconnect_everyone(&mut self) -> DateTime<Utc> {
+        let now = self.disconnect_host();
+        for player in self.players.values_mut() {
+            player.disconnected_at = Some(now);
+        }
+        now
+    }
+
+    /// Starts the grace period of the player `sid` and returns when it started.
+    pub fn disconnect_player(&mut self, sid: &Sid) -> Option<(&Player, DateTime<Utc>)> {
+        let now = Utc::now();
+        let player = self.players.get_mut(sid)?;
+        player.disconnected_at = Some(now);
+        Some((player, now))
+    }
+
+    /// Moves the player holding `token`, along with their board and team, to the new socket `sid`.
+    pub fn resume_player(
+        &mut self,
+        sid: Sid,
+        token: &ResumeToken,
+    ) -> Result<&Player, InvalidResumeToken> {
+        let previous = self
+            .players
+            .values()
+            .find(|player| player.token == *token)
+            .map(|player| player.id)
+            .ok_or(InvalidResumeToken)?;
+
+        let mut player = self.players.remove(&previous).ok_or(InvalidResumeToken)?;
+        player.id = sid;
+        player.disconnected_at = None;
+
+        if let Some(board) = self.boards.remove(&previous) {
+            self.boards.insert(sid, board);
+        }
+
+        for team in &mut self.teams {
+            for member in &mut team.members {
+                if *member == previous {
+                    *member = sid;
+                }
+            }
+        }
+
+        self.touch();
+        Ok(self.players.entry(sid).or_insert(player))
+    }
+
+    /// Makes the player `sid` the host. The previous host becomes a player without a board, unless
+    /// they left for good.
+    ///
+    /// Resume tokens move along: the new host takes the lobby back with the token they joined
+    /// with, the previous host takes their seat back with the host token.
+    pub fn transfer_host(
+        &mut self,
+        sid: Sid,
+        keep_previous: bool,
+    ) -> Result<(), TransferHostError> {
+        let player = self
+            .players
+            .get(&sid)
+            .ok_or(TransferHostError::NotAPlayer)?;
+        if player.disconnected_at.is_some() {
+            return Err(TransferHostError::Disconnected);
+        }
+
+        let player = self
+            .remove_player(&sid)
+            .ok_or(TransferHostError::NotAPlayer)?;
+
+        let previous = std::mem::replace(&mut self.host, Host::new(player.id, player.name));
+        let previous_token = std::mem::replace(&mut self.host_token, player.token);
+        self.host_disconnected_at = None;
+
+        if keep_previous {
+            let mut demoted = Player::new(previous.id, previous.name);
+            demoted.token = previous_token;
+            self.add_player(demoted);
+        }
+
+        Ok(())
+    }
+
+    /// The connected player who takes over when the host is gone, the first one by name.
+    pub fn promotion_candidate(&self) -> Option<Sid> {
+        self.players
+            .values()
+            .filter(|player| player.disconnected_at.is_none())
+            .min_by(|a, b| a.name.cmp(&b.name))
+            .map(|player| player.id)
+    }
+
+    pub fn role_of(&self, sid: Sid) -> Option<Role> {
+        if self.is_host(sid) {
+            Some(Role::Host)
+        } else if self.players.contains_key(&sid) {
+            Some(Role::Player)
+        } else if self.spectators.contains(&sid) {
+            Some(Role::Spectator)
+        } else {
+            None
+        }
+    }
+
+    /// The names of every player who has a board, sorted.
+    pub fn submitted_boards(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .boards
+            .keys()
+            .filter_map(|sid| self.players.get(sid))
+            .map(|player| player.name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Every board handed out so far, by the name of its player.
+    pub fn boards_by_name(&self) -> HashMap<String, &[CardId]> {
+        self.boards
+            .iter()
+            .filter_map(|(sid, board)| {
+                Some((self.players.get(sid)?.name.clone(), board.as_slice()))
+            })
+            .collect()
+    }
+
+    pub fn team_of(&self, sid: &Sid) -> Option<&Team> {
+        self.teams.iter().find(|team| team.members.contains(sid))
+    }
+
+    /// Moves the player `sid` into `team`, creating it if needed, or out of their team for `None`.
+    pub fn set_team(&mut self, sid: Sid, team: Option<&str>) -> Result<(), TeamError> {
+        if !self.settings.teams {
+            return Err(TeamError::TeamsDisabled);
+        }
+
+        if self.state != LobbyState::WaitingForPlayers {
+            return Err(TeamError::NotWaiting);
+        }
+
+        if !self.players.contains_key(&sid) {
+            return Err(TeamError::NotAPlayer);
+        }
+
+        let team = team.map(str::trim);
+        if team.is_some_and(|name| name.is_empty() || name.chars().count() > MAX_TEAM_NAME_LENGTH) {
+            return Err(TeamError::InvalidName);
+        }
+
+        self.leave_team(&sid);
+
+        if let Some(name) = team {
+            match self.teams.iter_mut().find(|team| team.name == name) {
+                Some(team) => team.members.push(sid),
+                None => self.teams.push(Team::new(name.to_string(), sid)),
+            }
+        }
+
+        self.touch();
+        Ok(())
+    }
+
+    fn leave_team(&mut self, sid: &Sid) {
+        for team in &mut self.teams {
+            team.members.retain(|member| member != sid);
+        }
+
+        self.teams.retain(|team| !team.members.is_empty());
+    }
+
+    /// Puts every player without a team into the smallest team, or into a team of their own if
+    /// nobody formed one.
+    fn fill_teams(&mut self) {
+        let mut teamless: Vec<&Player> = self
+            .players
+            .values()
+            .filter(|player| self.team_of(&player.id).is_none())
+            .collect();
+        teamless.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let teamless: Vec<(Sid, String)> = teamless
+            .into_iter()
+            .map(|player| (player.id, player.name.clone()))
+            .collect();
+
+        for (sid, name) in teamless {
+            match self.teams.iter_mut().min_by_key(|team| team.members.len()) {
+                Some(team) => team.members.push(sid),
+                None => self.teams.push(Team::new(name, sid)),
+            }
+        }
+    }
+
+    pub fn team_summaries(&self) -> Vec<TeamSummary> {
+        self.teams
+            .iter()
+            .map(|team| TeamSummary {
+                name: team.name.clone(),
+                members: self.member_names(team),
+            })
+            .collect()
+    }
+
+    fn member_names(&self, team: &Team) -> Vec<String> {
+        team.members
+            .iter()
+            .filter_map(|sid| self.players.get(sid))
+            .map(|player| player.name.clone())
+            .collect()
+    }
+
+    /// The name wins are awarded to: the player's team in team mode, the player otherwise.
+    fn contender(&self, sid: &Sid) -> Option<&str> {
+        let player = self.players.get(sid)?;
+
+        if self.settings.teams {
+            self.team_of(sid).map(|team| team.name.as_str())
+        } else {
+            Some(player.name.as_str())
+        }
+    }
+
+    pub fn has_card(&self, card_id: CardId) -> bool {
+        self.available_cards.iter().any(|card| card.id == card_id)
+    }
+
+    /// Checks that `cards` fill a board with distinct cards from the card pool.
+    pub fn validate_board(&self, cards: &[CardId]) -> Result<(), InvalidBoard> {
+        let expected = self.settings.cards_per_board();
+        if cards.len() != expected {
+            return Err(InvalidBoard::WrongLength {
+                expected,
+                got: cards.len(),
+            });
+        }
+
+        let mut seen = HashSet::with_capacity(cards.len());
+        for &card_id in cards {
+            if !self.has_card(card_id) {
+                return Err(InvalidBoard::UnknownCard(card_id));
+            }
+
+            if !seen.insert(card_id) {
+                return Err(InvalidBoard::DuplicateCard(card_id));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates and stores the board of the player `sid`, replacing any earlier board.
+    pub fn submit_board(
+        &mut self,
+        sid: Sid,
+        cards: Vec<CardId>,
+    ) -> Result<&Player, SubmitBoardError> {
+        if self.state != LobbyState::CraftingBoards {
+            return Err(SubmitBoardError::NotCrafting);
+        }
+
+        let Some(player) = self.players.get(&sid) else {
+            return Err(SubmitBoardError::NotAPlayer);
+        };
+
+        self.validate_board(&cards)?;
+
+        // Team members share a single board
+        let members = match self.team_of(&sid) {
+            Some(team) if self.settings.teams => team.members.clone(),
+            _ => vec![sid],
+        };
+
+        for member in members {
+            self.boards.insert(member, cards.clone());
+        }
+
+        self.last_activity = Utc::now();
+        Ok(player)
+    }
+
+    pub fn advance_state(&mut self) -> Result<LobbyState, LastStateReached> {
+        let mut state = self.state.next_stage().ok_or(LastStateReached)?;
+        self.touch();
+
+        if self.state == LobbyState::WaitingForPlayers && self.settings.teams {
+            self.fill_teams();
+        }
+
+        // Nothing to craft when the server hands out the boards
+        if state == LobbyState::CraftingBoards && self.settings.auto_boards {
+            state = LobbyState::InProgress;
+        }
+
+        if state == LobbyState::Completed {
+            self.complete();
+        } else {
+            self.state = state;
+            self.deadline = self
+                .settings
+                .deadlines
+                .seconds(state)
+                .map(|seconds| Utc::now() + chrono::Duration::seconds(seconds.into()));
+        }
+
+        Ok(self.state)
+    }
+
+    /// Ends the round and adds its placements to the scores.
+    pub fn complete(&mut self) {
+        self.state = LobbyState::Completed;
+        self.deadline = None;
+
+        for placement in &self.placements {
+            let (points, won) = self.points_for(placement);
+
+            match self
+                .scores
+                .iter_mut()
+                .find(|score| score.name == placement.winner.name)
+            {
+                Some(score) => {
+                    score.points += points;
+                    score.wins += won;
+                }
+                None => self.scores.push(Score {
+                    name: placement.winner.name.clone(),
+                    points,
+                    wins: won,
+                }),
+            }
+        }
+
+        self.sort_scores();
+    }
+
+    /// The points and wins a placement adds to the scores.
+    fn points_for(&self, placement: &Placement) -> (u32, u32) {
+        let places = usize::from(self.settings.places);
+        let points = (places + 1).saturating_sub(placement.place).max(1) as u32;
+        (points, u32::from(placement.place == 1))
+    }
+
+    fn sort_scores(&mut self) {
+        self.scores
+            .sort_by(|a, b| b.points.cmp(&a.points).then(b.wins.cmp(&a.wins)));
+    }
+
+    /// Takes back what [`Lobby::complete`] added to the scores for `placements`.
+    fn revoke_points(&mut self, placements: &[Placement]) {
+        for placement in placements {
+            let (points, won) = self.points_for(placement);
+
+            if let Some(score) = self
+                .scores
+                .iter_mut()
+                .find(|score| score.name == placement.winner.name)
+            {
+                score.points = score.points.saturating_sub(points);
+                score.wins = score.wins.saturating_sub(won);
+            }
+        }
+
+        // Every placement is worth a point, so nobody with a score ends up at zero otherwise
+        self.scores.retain(|score| score.points > 0);
+        self.sort_scores();
+    }
+
+    /// Starts another round with the same players, and optionally a new card pool. Boards,
+    /// answers and placements are cleared, the scores are kept.
+    pub fn start_new_round(&mut self, cards: Option<Vec<Card>>) -> Result<u32, RoundNotCompleted> {
+        if self.state != LobbyState::Completed {
+            return Err(RoundNotCompleted);
+        }
+
+        if let Some(cards) = cards {
+            self.available_cards = cards;
+        }
+
+        self.boards.clear();
+        self.correct_answers.clear();
+        self.placements.clear();
+        self.state = LobbyState::WaitingForPlayers;
+        self.round += 1;
+        self.touch();
+
+        Ok(self.round)
+    }
+
+    /// Picks a board from the card pool. The same seed always yields the same board for the same
+    /// card pool and settings. ChaCha8 is used because, unlike `StdRng`, its output is fixed
+    /// across versions and platforms.
+    pub fn generate_board(&self, seed: Option<BoardSeed>) -> GeneratedBoard {
+        let seed = seed.unwrap_or_else(|| rand::rng().random());
+        let mut rng = ChaCha8Rng::seed_from_u64(seed.into());
+
+        let cards = index::sample(
+            &mut rng,
+            self.available_cards.len(),
+            self.settings.cards_per_board(),
+        )
+        .into_iter()
+        .map(|idx| self.available_cards[idx].id)
+        .collect();
+
+        GeneratedBoard { seed, cards }
+    }
+
+    /// Gives every player without a board a random one. Team members get the same board.
+    pub fn assign_random_boards(&mut self) -> Vec<(Sid, GeneratedBoard)> {
+        let mut assigned: Vec<(Sid, GeneratedBoard)> = Vec::new();
+
+        let mut unassigned: Vec<Sid> = self
+            .players
+            .keys()
+            .filter(|sid| !self.boards.contains_key(sid))
+            .copied()
+            .collect();
+
+        while let Some(sid) = unassigned.pop() {
+            let members = match self.team_of(&sid) {
+                Some(team) if self.settings.teams => team.members.clone(),
+                _ => vec![sid],
+            };
+
+            let board = self.generate_board(None);
+            for member in members {
+                unassigned.retain(|other| *other != member);
+                self.boards.insert(member, board.cards.clone());
+                assigned.push((member, board.clone()));
+            }
+        }
+
+        assigned
+    }
+
+    pub fn check_winners(&self) -> Vec<Winner> {
+        let mut winners: Vec<Winner> = Vec::new();
+
+        for (player_id, board) in &self.boards {
+            if let Some(cells) = check_winner_board(
+                board,
+                self.settings.board_size,
+                self.settings.win_pattern,
+                self.settings.free_center,
+                &self.correct_answers,
+            ) && let Some(name) = self.contender(player_id)
+                && !winners.iter().any(|winner| winner.name == name)
+            {
+                let members = match self.team_of(player_id) {
+                    Some(team) if self.settings.teams => self.member_names(team),
+                    _ => Vec::new(),
+                };
+
+                winners.push(Winner {
+                    name: name.to_string(),
+                    members,
+                    pattern: self.settings.win_pattern,
+                    cells,
+                });
+            }
+        }
+
+        winners
+    }
+
+    /// Marks `card_id` as a correct answer. Returns `false` if it had already been submitted.
+    pub fn submit_answer(&mut self, card_id: CardId) -> Result<bool, SubmitAnswerError> {
+        if self.state != LobbyState::InProgress {
+            return Err(SubmitAnswerError::NotInProgress);
+        }
+
+        if !self.has_card(card_id) {
+            return Err(SubmitAnswerError::UnknownCard(card_id));
+        }
+
+        if self.correct_answers.contains(&card_id) {
+            return Ok(false);
+        }
+
+        self.correct_answers.push(card_id);
+        self.touch();
+        Ok(true)
+    }
+
+    pub fn has_placed(&self, name: &str) -> bool {
+        self.placements.iter().any(|p| p.winner.name == name)
+    }
+
+    /// Awards the next place to every player who won since the last call and returns the new
+    /// placements.
+    pub fn record_placements(&mut self) -> Vec<Placement> {
+        let place = self.placements.len() + 1;
+        let answer_index = self.correct_answers.len().saturating_sub(1);
+        let won_at = Utc::now();
+
+        let new_placements: Vec<Placement> = self
+            .check_winners()
+            .into_iter()
+            .filter(|winner| !self.has_placed(&winner.name))
+            .map(|winner| Placement {
+                place,
+                winner,
+                answer_index,
+                won_at,
+            })
+            .collect();
+
+        self.placements.extend(new_placements.iter().cloned());
+        new_placements
+    }
+
+    /// Every player without a placement who is a single card away from winning.
+    pub fn near_wins(&self) -> Vec<NearWin> {
+        let mut near_wins = Vec::new();
+
+        for (player_id, board) in &self.boards {
+            let Some(name) = self.contender(player_id) else {
+                continue;
+            };
+
+            if self.has_placed(name) || near_wins.iter().any(|near: &NearWin| near.name == name) {
+                continue;
+            }
+
+            let cards = finishing_cards(
+                board,
+                self.settings.board_size,
+                self.settings.win_pattern,
+                self.settings.free_center,
+                &self.correct_answers,
+            );
+
+            if !cards.is_empty() {
+                near_wins.push(NearWin {
+                    name: name.to_string(),
+                    cards,
+                });
+            }
+        }
+
+        near_wins
+    }
+
+    /// Retracts a correct answer and returns the players who lost their placement.
+    ///
+    /// A completed game only takes back answers that cost someone their place. It's reopened and
+    /// the points of the round are taken back, unless every place is filled again right away.
+    pub fn retract_answer(&mut self, card_id: CardId) -> Result<Vec<String>, RetractAnswerError> {
+        match self.state {
+            LobbyState::InProgress => self
+                .remove_answer(card_id)
+                .ok_or(RetractAnswerError::NotSubmitted),
+            LobbyState::Completed => {
+                let (answers, placements) = (self.correct_answers.clone(), self.placements.clone());
+
+                let revoked = self
+                    .remove_answer(card_id)
+                    .ok_or(RetractAnswerError::NotSubmitted)?;
+                if revoked.is_empty() {
+                    self.correct_answers = answers;
+                    self.placements = placements;
+                    return Err(RetractAnswerError::NoPlaceRevoked);
+                }
+
+                self.revoke_points(&placements);
+                self.state = LobbyState::InProgress;
+
+                // A later answer may already fill the place that was freed up
+                if self.placements_filled() {
+                    self.complete();
+                }
+
+                Ok(revoked)
+            }
+            _ => Err(RetractAnswerError::WrongStage),
+        }
+    }
+
+    /// Removes `card_id` from the correct answers and recomputes the placements as if it had
+    /// never been submitted. Returns the names of the players who lost their placement, or `None`
+    /// if the card was never submitted.
+    fn remove_answer(&mut self, card_id: CardId) -> Option<Vec<String>> {
+        let retracted_index = self.correct_answers.iter().position(|&id| id == card_id)?;
+
+        let previous = std::mem::take(&mut self.placements);
+        let answers = std::mem::take(&mut self.correct_answers);
+
+        // Replay the remaining answers, so places and answer indices shift accordingly
+        for answer in answers
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, id)| (idx != retracted_index).then_some(id))
+        {
+            self.correct_answers.push(answer);
+            self.record_placements();
+        }
+
+        // Players who still won on the same answer keep the time of their win
+        for placement in &mut self.placements {
+            if let Some(old) = previous
+                .iter()
+                .find(|old| old.winner.name == placement.winner.name)
+                && old.answer_index - usize::from(old.answer_index > retracted_index)
+                    == placement.answer_index
+            {
+                placement.won_at = old.won_at;
+            }
+        }
+
+        self.touch();
+
+        let revoked = previous
+            .into_iter()
+            .filter(|old| !self.has_placed(&old.winner.name))
+            .map(|old| old.winner.name)
+            .collect();
+
+        Some(revoked)
+    }
+
+    /// Whether every place has been awarded, or nobody is left who could still win one.
+    pub fn placements_filled(&self) -> bool {
+        self.placements.len() >= self.settings.places as usize
+            || !self.placements.is_empty()
+                && self
+                    .boards
+                    .keys()
+                    .all(|sid| self.contender(sid).is_none_or(|name| self.has_placed(name)))
+    }
+}
+
+/// Whether `candidate` was given and matches the `expected` secret, if one is set.
+fn secret_matches(expected: &Option<Secret>, candidate: Option<&Secret>) -> bool {
+    match (expected, candidate) {
+        (Some(expected), Some(candidate)) => expected.matches(candidate),
+        _ => false,
+    }
+}
+
+/// Returns the card in `cell`, or `None` for the free center.
+///
+/// With a `free_center`, the board leaves out the center cell, which always counts as marked.
+fn card_at(board: &[CardId], size: BoardSize, free_center: bool, cell: usize) -> Option<CardId> {
+    let center = size.cell_count() / 2;
+
+    match cell.cmp(&center) {
+        Ordering::Equal if free_center => None,
+        Ordering::Greater if free_center => Some(board[cell - 1]),
+        _ => Some(board[cell]),
+    }
+}
+
+/// Returns the cells that complete `pattern` on `board`, if it is fulfilled.
+fn check_winner_board(
+    board: &[CardId],
+    size: BoardSize,
+    pattern: WinPattern,
+    free_center: bool,
+    correct_answers: &[CardId],
+) -> Option<Vec<usize>> {
+    let is_marked = |cell: &usize| {
+        card_at(board, size, free_center, *cell).is_none_or(|id| correct_answers.contains(&id))
+    };
+
+    // Shape patterns need every one of their cells marked
+    if let Some(shape) = pattern.shape(size) {
+        return shape.iter().all(is_marked).then_some(shape);
+    }
+
+    // Line patterns need a number of complete rows, columns or diagonals
+    let required_lines = match pattern {
+        WinPattern::TwoLines => 2,
+        _ => 1,
+    };
+
+    let completed: Vec<Vec<usize>> = size
+        .lines()
+        .into_iter()
+        .filter(|line| line.iter().all(is_marked))
+        .take(required_lines)
+        .collect();
+
+    if completed.len() < required_lines {
+        return None;
+    }
+
+    let mut cells = completed.concat();
+    cells.sort_unstable();
+    cells.dedup();
+    Some(cells)
+}
+
+/// Returns every card that would complete `pattern` on `board` on its own. Empty if the pattern
+/// is already fulfilled or needs more than one card.
+fn finishing_cards(
+    board: &[CardId],
+    size: BoardSize,
+    pattern: WinPattern,
+    free_center: bool,
+    correct_answers: &[CardId],
+) -> Vec<CardId> {
+    if check_winner_board(board, size, pattern, free_center, correct_answers).is_some() {
+        return Vec::new();
+    }
+
+    let missing = |cells: &[usize]| -> Vec<CardId> {
+        cells
+            .iter()
+            .filter_map(|&cell| card_at(board, size, free_center, cell))
+            .filter(|id| !correct_answers.contains(id))
+            .collect()
+    };
+
+    let mut cards = if let Some(shape) = pattern.shape(size) {
+        let missing = missing(&shape);
+        if missing.len() == 1 {
+            missing
+        } else {
+            Vec::new()
+        }
+    } else {
+        // The cards each row, column and diagonal is still missing
+        let progress: Vec<Vec<CardId>> = size.lines().iter().map(|line| missing(line)).collect();
+        let completed = progress.iter().filter(|line| line.is_empty()).count();
+        let one_away: Vec<CardId> = progress
+            .iter()
+            .filter(|line| line.len() == 1)
+            .map(|line| line[0])
+            .collect();
+
+        match pattern {
+            // Without a complete line, the card has to finish two lines at once
+            WinPattern::TwoLines if completed == 0 => one_away
+                .iter()
+                .copied()
+                .filter(|id| one_away.iter().filter(|other| *other == id).count() >= 2)
+                .collect(),
+            _ => one_away,
+        }
+    };
+
+    cards.sort_unstable();
+    cards.dedup();
+    cards
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct Host {
+    pub name: String,
+    pub id: Sid,
+}
+
+impl Host {
+    pub fn new(id: Sid, name: String) -> Self {
+        Self { id, name }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct Player {
+    pub id: Sid,
+    pub name: String,
+
+    /// Lets the player take their seat back after reconnecting.
+    pub token: ResumeToken,
+
+    /// Set while the player is disconnected and may still come back.
+    pub disconnected_at: Option<DateTime<Utc>>,
+}
+
+impl Player {
+    pub fn new(id: Sid, name: String) -> Self {
+        Self {
+            id,
+            name,
+            token: ResumeToken::new(),
+            disconnected_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct Team {
+    pub name: String,
+    pub members: Vec<Sid>,
+}
+
+impl Team {
+    pub fn new(name: String, founder: Sid) -> Self {
+        Self {
+            name,
+            members: vec![founder],
+        }
+    }
+}
+
+/// A team as shown to clients, with member names instead of socket ids.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamSummary {
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+/// Sent along with every event emitted to a lobby.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventMeta {
+    /// Increases by one with every event of the lobby, starting at 1.
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedEvent {
+    #[serde(flatten)]
+    pub meta: EventMeta,
+    pub event: String,
+    pub data: serde_json::Value,
+
+    /// The only socket the event was sent to, if it wasn't sent to the whole lobby.
+    #[serde(skip)]
+    pub recipient: Option<Sid>,
+}
+
+/// A bounded log of the events emitted to a lobby. Only the sequence number is persisted, the
+/// events themselves don't survive a restart.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventLog {
+    last_seq: u64,
+
+    #[serde(skip)]
+    events: VecDeque<LoggedEvent>,
+
+    /// Set once `last_seq` moved on, so the lobby is saved before the number can be handed out
+    /// again after a restart.
+    #[serde(skip)]
+    unsaved: bool,
+}
+
+impl EventLog {
+    /// Records an event and returns the metadata to send along with it.
+    pub fn record(
+        &mut self,
+        event: &str,
+        data: serde_json::Value,
+        recipient: Option<Sid>,
+    ) -> EventMeta {
+        self.last_seq += 1;
+        self.unsaved = true;
+        let meta = EventMeta {
+            seq: self.last_seq,
+            timestamp: Utc::now(),
+        };
+
+        if self.events.len() == MAX_LOGGED_EVENTS {
+            self.events.pop_front();
+        }
+
+        self.events.push_back(LoggedEvent {
+            meta,
+            event: event.to_string(),
+            data,
+            recipient,
+        });
+
+        meta
+    }
+
+    /// The sequence number of the latest event, 0 before the first one.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// The events after `seq` that were meant for `sid`, and whether none of them were dropped
+    /// from the log already.
+    pub fn since(&self, seq: u64, sid: Sid) -> (Vec<&LoggedEvent>, bool) {
+        let oldest = self.last_seq + 1 - self.events.len() as u64;
+        // `seq` comes from the client and may be anything
+        let complete = seq.saturating_add(1) >= oldest;
+
+        let events = self
+            .events
+            .iter()
+            .filter(|logged| logged.meta.seq > seq)
+            .filter(|logged| logged.recipient.is_none_or(|recipient| recipient == sid))
+            .collect();
+
+        (events, complete)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{
+        DateTime,
+        Utc,
+    };
+    use socketioxide::socket::Sid;
+
+    use crate::model::{
+        BoardSize,
+        Card,
+        CardId,
+        CloseReason,
+        EventLog,
+        Host,
+        InvalidSettings,
+        JoinError,
+        Lobby,
+        LobbySettings,
+        LobbyState,
+        MAX_LOGGED_EVENTS,
+        Player,
+        ResumeToken,
+        RetractAnswerError,
+        Role,
+        Score,
+        Secret,
+        StageDeadlines,
+        SubmitAnswerError,
+        SubmitBoardError,
+        Team,
+        TeamError,
+        TransferHostError,
+        WaitlistEntry,
+        WinPattern,
+        check_winner_board,
+        finishing_cards,
+    };
+
+    /// Line bingo without a free center.
+    fn wins_line(board: &[CardId], size: BoardSize, correct_answers: &[CardId]) -> bool {
+        check_winner_board(board, size, WinPattern::AnyLine, false, correct_answers).is_some()
+    }
+
+    #[rustfmt::skip]
+    const fn sample_board() -> [CardId; 25] {
+        [
+        // Columns
+        //   0   1   2   3   4  // rows
+             1,  2,  3,  4,  5, // 0
+             6,  7,  8,  9, 10, // 1
+            11, 12, 13, 14, 15, // 2
+            16, 17, 18, 19, 20, // 3
+            21, 22, 23, 24, 25, // 4
+        ]
+    }
+
+    #[test]
+    fn row_win_detected() {
+        let board = sample_board();
+        // row 1 (second row): 6,7,8,9,10 scrambled
+        let correct = vec![9, 6, 10, 7, 8];
+        assert!(wins_line(&board, BoardSize::Five, &correct));
+    }
+
+    #[test]
+    fn column_win_detected() {
+        let board = sample_board();
+        // column 2 (third column): values 3,8,13,18,23 scrambled
+        let correct = vec![18, 3, 23, 8, 13];
+        assert!(wins_line(&board, BoardSize::Five, &correct));
+    }
+
+    #[test]
+    fn main_diagonal_win_detected() {
+        let board = sample_board();
+        // main diagonal: values 1,7,13,19,25 scrambled
+        let correct = vec![13, 25, 1, 19, 7];
+        assert!(wins_line(&board, BoardSize::Five, &correct));
+    }
+
+    #[test]
+    fn other_diagonal_win_detected() {
+        let board = sample_board();
+        // other diagonal: values 5,9,13,17,21 scrambled
+        let correct = vec![21, 13, 5, 17, 9];
+        assert!(wins_line(&board, BoardSize::Five, &correct));
+    }
+
+    #[test]
+    fn no_win_detected() {
+        let board = sample_board();
+        // scattered answers that don't make any full row/col/diagonal
+        let correct = vec![14, 1, 22, 6, 3]; // scrambled scattered set
+        assert!(!wins_line(&board, BoardSize::Five, &correct));
+    }
+
+    #[test]
+    fn small_board_row_win_detected() {
+        let board = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        // row 2 (last row): 7,8,9 scrambled
+        let correct = vec![8, 9, 7];
+        assert!(wins_line(&board, BoardSize::Three, &correct));
+    }
+
+    #[test]
+    fn large_board_other_diagonal_win_detected() {
+        let board: Vec<CardId> = (1..=49).collect();
+        // other diagonal of a 7x7 board: 7,13,19,25,31,37,43
+        let correct = vec![43, 7, 25, 19, 37, 13, 31];
+        assert!(wins_line(&board, BoardSize::Seven, &correct));
+        assert!(!wins_line(&board, BoardSize::Seven, &correct[1..]));
+    }
+
+    #[test]
+    fn two_lines_need_two_complete_lines() {
+        let board = sample_board();
+        // row 0 and column 0 share the card 1
+        let one_line = vec![1, 2, 3, 4, 5];
+        let two_lines = vec![1, 2, 3, 4, 5, 6, 11, 16, 21];
+
+        let pattern = WinPattern::TwoLines;
+        assert!(check_winner_board(&board, BoardSize::Five, pattern, false, &one_line).is_none());
+        assert_eq!(
+            check_winner_board(&board, BoardSize::Five, pattern, false, &two_lines)
+                .map(|c| c.len()),
+            Some(9)
+        );
+    }
+
+    #[test]
+    fn four_corners_win_detected() {
+        let board = sample_board();
+        let correct = vec![25, 1, 21, 5];
+        assert_eq!(
+            check_winner_board(
+                &board,
+                BoardSize::Five,
+                WinPattern::FourCorners,
+                false,
+                &correct
+            ),
+            Some(vec![0, 4, 20, 24])
+        );
+    }
+
+    #[test]
+    fn x_shape_needs_both_diagonals() {
+        let board = sample_board();
+        let main_diagonal = vec![1, 7, 13, 19, 25];
+        let both_diagonals = vec![1, 7, 13, 19, 25, 5, 9, 17, 21];
+
+        let pattern = WinPattern::XShape;
+        assert!(
+            check_winner_board(&board, BoardSize::Five, pattern, false, &main_diagonal).is_none()
+        );
+        assert!(
+            check_winner_board(&board, BoardSize::Five, pattern, false, &both_diagonals).is_some()
+        );
+    }
+
+    #[test]
+    fn plus_and_frame_win_detected() {
+        let board = sample_board();
+        let plus = vec![3, 8, 11, 12, 13, 14, 15, 18, 23];
+        let frame = vec![1, 2, 3, 4, 5, 6, 10, 11, 15, 16, 20, 21, 22, 23, 24, 25];
+
+        let size = BoardSize::Five;
+        assert!(check_winner_board(&board, size, WinPattern::Plus, false, &plus).is_some());
+        assert!(check_winner_board(&board, size, WinPattern::Frame, false, &plus).is_none());
+        assert!(check_winner_board(&board, size, WinPattern::Frame, false, &frame).is_some());
+    }
+
+    #[test]
+    fn blackout_needs_every_card() {
+        let board = sample_board();
+        let all: Vec<CardId> = (1..=25).collect();
+
+        let pattern = WinPattern::Blackout;
+        assert!(check_winner_board(&board, BoardSize::Five, pattern, false, &all[1..]).is_none());
+        assert!(check_winner_board(&board, BoardSize::Five, pattern, false, &all).is_some());
+    }
+
+    #[test]
+    fn custom_mask_win_detected() {
+        let board = sample_board();
+        // cells 0, 6 and 12
+        let pattern = WinPattern::Custom {
+            mask: 0b1_0000_0100_0001,
+        };
+        assert!(check_winner_board(&board, BoardSize::Five, pattern, false, &[1, 7, 13]).is_some());
+        assert!(check_winner_board(&board, BoardSize::Five, pattern, false, &[1, 7]).is_none());
+    }
+
+    #[test]
+    fn invalid_win_patterns_rejected() {
+        assert!(WinPattern::Plus.validate(BoardSize::Four).is_err());
+        assert!(
+            WinPattern::Custom { mask: 0 }
+                .validate(BoardSize::Five)
+                .is_err()
+        );
+        assert!(
+            WinPattern::Custom { mask: 1 << 9 }
+                .validate(BoardSize::Three)
+                .is_err()
+        );
+        assert!(
+            WinPattern::Custom { mask: 1 << 8 }
+                .validate(BoardSize::Three)
+                .is_ok()
+        );
+    }
+
+    fn lobby_with_boards(places: u8, boards: &[(&str, [CardId; 25])]) -> Lobby {
+        let settings = LobbySettings {
+            places,
+            ..LobbySettings::default()
+        };
+        let mut lobby = Lobby::new(Host::new(Sid::new(), "host".into()), settings, Vec::new());
+        lobby.state = LobbyState::InProgress;
+
+        for (name, board) in boards {
+            let sid = Sid::new();
+            lobby
+                .players
+                .insert(sid, Player::new(sid, name.to_string()));
+            lobby.boards.insert(sid, board.to_vec());
+        }
+
+        lobby
+    }
+
+    #[test]
+    fn placements_are_recorded_once_in_order() {
+        // Moves card 1 from the first row into the last one
+        let mut swapped = sample_board();
+        swapped.swap(0, 24);
+        let mut lobby = lobby_with_boards(2, &[("first", sample_board()), ("second", swapped)]);
+
+        for answer in [1, 2, 3, 4, 5] {
+            lobby.correct_answers.push(answer);
+            lobby.record_placements();
+        }
+
+        assert_eq!(lobby.placements.len(), 1);
+        assert_eq!(lobby.placements[0].place, 1);
+        assert_eq!(lobby.placements[0].winner.name, "first");
+        assert_eq!(lobby.placements[0].answer_index, 4);
+        assert!(!lobby.placements_filled());
+
+        // Completing another line must not award "first" a second place
+        for answer in [21, 22, 23, 24, 25] {
+            lobby.correct_answers.push(answer);
+            lobby.record_placements();
+        }
+
+        assert_eq!(lobby.placements.len(), 2);
+        assert_eq!(lobby.placements[1].place, 2);
+        assert_eq!(lobby.placements[1].winner.name, "second");
+        assert!(lobby.placements_filled());
+    }
+
+    #[test]
+    fn simultaneous_winners_share_a_place() {
+        let mut lobby = lobby_with_boards(3, &[("a", sample_board()), ("b", sample_board())]);
+
+        lobby.correct_answers.extend([1, 2, 3, 4, 5]);
+        let new_placements = lobby.record_placements();
+
+        assert_eq!(new_placements.len(), 2);
+        assert!(new_placements.iter().all(|p| p.place == 1));
+        // Nobody is left who could win the third place
+        assert!(lobby.placements_filled());
+    }
+
+    #[test]
+    fn free_center_counts_as_marked() {
+        // 1..=24 around the free center
+        let board: Vec<CardId> = (1..=24).collect();
+        let size = BoardSize::Five;
+        let pattern = WinPattern::AnyLine;
+
+        // row 2: 11, 12, free, 13, 14
+        let row = vec![11, 12, 13, 14];
+        assert_eq!(
+            check_winner_board(&board, size, pattern, true, &row),
+            Some(vec![10, 11, 12, 13, 14])
+        );
+
+        // column 2: 3, 8, free, 17, 22
+        let column = vec![3, 8, 17, 22];
+        assert!(check_winner_board(&board, size, pattern, true, &column).is_some());
+        assert!(check_winner_board(&board, size, pattern, true, &column[1..]).is_none());
+    }
+
+    #[test]
+    fn free_center_needs_a_center_cell() {
+        let settings = LobbySettings {
+            board_size: BoardSize::Four,
+            free_center: true,
+            ..LobbySettings::default()
+        };
+        assert!(settings.validate().is_err());
+
+        let settings = LobbySettings {
+            board_size: BoardSize::Three,
+            ..settings
+        };
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.cards_per_board(), 8);
+    }
+
+    #[test]
+    fn boards_are_picked_from_the_pool() {
+        let settings = LobbySettings {
+            board_size: BoardSize::Three,
+            ..LobbySettings::default()
+        };
+        let pool = (0..12)
+            .map(|id| Card::new(format!("card {id}"), id))
+            .collect();
+        let lobby = Lobby::new(Host::new(Sid::new(), "host".into()), settings, pool);
+
+        assert!(lobby.validate_board(&[11, 0, 3, 5, 7, 9, 2, 4, 10]).is_ok());
+        assert!(lobby.validate_board(&[0, 1, 2, 3, 4, 5, 6, 7]).is_err());
+        assert!(lobby.validate_board(&[0, 1, 2, 3, 4, 5, 6, 7, 12]).is_err());
+        assert!(lobby.validate_board(&[0, 1, 2, 3, 4, 5, 6, 7, 7]).is_err());
+    }
+
+    #[test]
+    fn boards_are_only_accepted_from_players_while_crafting() {
+        let mut lobby = lobby_with_boards(1, &[]);
+        let host = lobby.host.id;
+        let player = Sid::new();
+        lobby
+            .players
+            .insert(player, Player::new(player, "player".into()));
+        lobby.available_cards = (1..=25).map(|id| Card::new(id.to_string(), id)).collect();
+        let board = sample_board().to_vec();
+
+        // `lobby_with_boards` starts the game, so boards are locked
+        assert!(matches!(
+            lobby.submit_board(player, board.clone()),
+            Err(SubmitBoardError::NotCrafting)
+        ));
+
+        lobby.state = LobbyState::CraftingBoards;
+        assert!(matches!(
+            lobby.submit_board(host, board.clone()),
+            Err(SubmitBoardError::NotAPlayer)
+        ));
+        assert!(matches!(
+            lobby.submit_board(player, board[1..].to_vec()),
+            Err(SubmitBoardError::InvalidBoard(_))
+        ));
+        assert!(lobby.submit_board(player, board).is_ok());
+        assert!(lobby.boards.contains_key(&player));
+    }
+
+    #[test]
+    fn retracting_an_answer_revokes_dependent_wins() {
+        // Moves card 1 from the first row into the last one
+        let mut swapped = sample_board();
+        swapped.swap(0, 24);
+        let mut lobby = lobby_with_boards(3, &[("first", sample_board()), ("second", swapped)]);
+
+        for answer in [1, 2, 3, 4, 5, 21, 22, 23, 24, 25] {
+            lobby.correct_answers.push(answer);
+            lobby.record_placements();
+        }
+        assert_eq!(lobby.placements.len(), 2);
+
+        // "first" still completes the last row, but only after "second" did
+        assert_eq!(lobby.retract_answer(2), Ok(Vec::new()));
+        assert!(!lobby.correct_answers.contains(&2));
+        assert_eq!(lobby.placements[0].winner.name, "second");
+        assert_eq!(lobby.placements[0].answer_index, 7);
+        assert_eq!(lobby.placements[1].winner.name, "first");
+        assert_eq!(lobby.placements[1].place, 2);
+        assert_eq!(lobby.placements[1].answer_index, 8);
+
+        let mut revoked = lobby.retract_answer(21).unwrap();
+        revoked.sort();
+        assert_eq!(revoked, ["first", "second"]);
+        assert!(lobby.placements.is_empty());
+
+        assert_eq!(
+            lobby.retract_answer(2),
+            Err(RetractAnswerError::NotSubmitted)
+        );
+    }
+
+    #[test]
+    fn retracting_the_winning_answer_reopens_the_game() {
+        let mut lobby = lobby_with_boards(1, &[("player", sample_board())]);
+        lobby.scores.push(Score {
+            name: "player".into(),
+            points: 1,
+            wins: 1,
+        });
+
+        for answer in [1, 2, 3, 4, 5] {
+            lobby.correct_answers.push(answer);
+            lobby.record_placements();
+        }
+        assert!(lobby.placements_filled());
+        lobby.complete();
+        assert_eq!(lobby.scores[0].points, 2);
+
+        // Taking back an answer nobody's win depends on changes nothing
+        lobby.correct_answers.push(25);
+        assert_eq!(
+            lobby.retract_answer(25),
+            Err(RetractAnswerError::NoPlaceRevoked)
+        );
+        assert_eq!(lobby.state, LobbyState::Completed);
+
+        assert_eq!(lobby.retract_answer(3).unwrap(), ["player"]);
+        assert_eq!(lobby.state, LobbyState::InProgress);
+        assert!(lobby.placements.is_empty());
+        assert_eq!(lobby.scores[0].points, 1);
+        assert_eq!(lobby.scores[0].wins, 1);
+
+        // Points of a first round are taken back entirely
+        lobby.scores.clear();
+        lobby.correct_answers.push(3);
+        lobby.record_placements();
+        lobby.complete();
+        lobby.retract_answer(1).unwrap();
+        assert!(lobby.scores.is_empty());
+    }
+
+    #[test]
+    fn answers_are_validated_and_idempotent() {
+        let mut lobby = lobby_with_boards(1, &[]);
+        lobby.available_cards = (0..25).map(|id| Card::new(id.to_string(), id)).collect();
+
+        assert!(matches!(lobby.submit_answer(3), Ok(true)));
+        assert!(matches!(lobby.submit_answer(3), Ok(false)));
+        assert_eq!(lobby.correct_answers, [3]);
+
+        assert!(matches!(
+            lobby.submit_answer(25),
+            Err(SubmitAnswerError::UnknownCard(25))
+        ));
+
+        lobby.state = LobbyState::Completed;
+        assert!(matches!(
+            lobby.submit_answer(4),
+            Err(SubmitAnswerError::NotInProgress)
+        ));
+    }
+
+    #[test]
+    fn finishing_card_detected_per_line() {
+        let board = sample_board();
+        let size = BoardSize::Five;
+        let pattern = WinPattern::AnyLine;
+
+        // row 0 misses 5, column 0 misses 21
+        let correct = vec![1, 2, 3, 4, 6, 11, 16];
+        assert_eq!(
+            finishing_cards(&board, size, pattern, false, &correct),
+            [5, 21]
+        );
+
+        // Two cards away from everything
+        assert!(finishing_cards(&board, size, pattern, false, &[1, 2, 3]).is_empty());
+
+        // Already won
+        let won = vec![1, 2, 3, 4, 5, 6, 11, 16];
+        assert!(finishing_cards(&board, size, pattern, false, &won).is_empty());
+    }
+
+    #[test]
+    fn finishing_card_for_two_lines_and_shapes() {
+        let board = sample_board();
+        let size = BoardSize::Five;
+
+        // row 0 and column 0 both only miss card 1
+        let correct = vec![2, 3, 4, 5, 6, 11, 16, 21];
+        assert_eq!(
+            finishing_cards(&board, size, WinPattern::TwoLines, false, &correct),
+            [1]
+        );
+
+        // row 0 only misses 5, but there is no other complete line
+        let correct = vec![1, 2, 3, 4];
+        assert!(finishing_cards(&board, size, WinPattern::TwoLines, false, &correct).is_empty());
+
+        let corners = vec![1, 5, 21];
+        assert_eq!(
+            finishing_cards(&board, size, WinPattern::FourCorners, false, &corners),
+            [25]
+        );
+    }
+
+    #[test]
+    fn finishing_card_with_free_center() {
+        let board: Vec<CardId> = (1..=24).collect();
+
+        // row 2: 11, 12, free, 13, 14
+        let correct = vec![11, 12, 13];
+        assert_eq!(
+            finishing_cards(&board, BoardSize::Five, WinPattern::AnyLine, true, &correct),
+            [14]
+        );
+    }
+
+    #[test]
+    fn generated_boards_are_reproducible() {
+        let settings = LobbySettings {
+            free_center: true,
+            ..LobbySettings::default()
+        };
+        let pool = (0..40).map(|id| Card::new(id.to_string(), id)).collect();
+        let lobby = Lobby::new(Host::new(Sid::new(), "host".into()), settings, pool);
+
+        let board = lobby.generate_board(Some(42));
+        assert_eq!(board.seed, 42);
+        assert!(lobby.validate_board(&board.cards).is_ok());
+        assert_eq!(lobby.generate_board(Some(42)), board);
+
+        let random = lobby.generate_board(None);
+        assert_eq!(lobby.generate_board(Some(random.seed)), random);
+
+        // Shared seeds have to keep working across releases
+        assert_eq!(board.cards[..5], [8, 27, 7, 38, 31]);
+    }
+
+    #[test]
+    fn auto_boards_skip_crafting() {
+        let settings = LobbySettings {
+            auto_boards: true,
+            ..LobbySettings::default()
+        };
+        let pool = (0..25).map(|id| Card::new(id.to_string(), id)).collect();
+        let mut lobby = Lobby::new(Host::new(Sid::new(), "host".into()), settings, pool);
+        for name in ["a", "b"] {
+            let sid = Sid::new();
+            lobby.players.insert(sid, Player::new(sid, name.into()));
+        }
+
+        assert_eq!(lobby.advance_state().unwrap(), LobbyState::InProgress);
+
+        let assigned = lobby.assign_random_boards();
+        assert_eq!(assigned.len(), 2);
+        assert_eq!(lobby.boards.len(), 2);
+        assert!(lobby.assign_random_boards().is_empty());
+    }
+
+    #[test]
+    fn teams_share_boards_and_win_together() {
+        let settings = LobbySettings {
+            teams: true,
+            ..LobbySettings::default()
+        };
+        let pool = (1..=25).map(|id| Card::new(id.to_string(), id)).collect();
+        let mut lobby = Lobby::new(Host::new(Sid::new(), "host".into()), settings, pool);
+
+        let [a, b, c] = [Sid::new(), Sid::new(), Sid::new()];
+        for (sid, name) in [(a, "a"), (b, "b"), (c, "c")] {
+            lobby.players.insert(sid, Player::new(sid, name.into()));
+        }
+
+        lobby.set_team(a, Some("red")).unwrap();
+        lobby.set_team(b, Some(" blue ")).unwrap();
+        assert!(matches!(
+            lobby.set_team(c, Some("  ")),
+            Err(TeamError::InvalidName)
+        ));
+
+        // "c" is put into a team on their own
+        assert_eq!(lobby.advance_state().unwrap(), LobbyState::CraftingBoards);
+        assert_eq!(lobby.teams.len(), 2);
+        assert!(lobby.team_of(&c).is_some());
+        assert!(matches!(
+            lobby.set_team(c, None),
+            Err(TeamError::NotWaiting)
+        ));
+
+        let red = lobby.team_of(&a).unwrap().clone();
+        let teammate = *red.members.iter().find(|sid| **sid != a).unwrap_or(&a);
+
+        lobby.submit_board(a, sample_board().to_vec()).unwrap();
+        assert_eq!(lobby.boards.get(&teammate), lobby.boards.get(&a));
+
+        lobby.state = LobbyState::InProgress;
+        lobby.correct_answers.extend([1, 2, 3, 4, 5]);
+        let winners = lobby.check_winners();
+        assert_eq!(winners.len(), 1);
+        assert_eq!(winners[0].name, "red");
+        assert_eq!(winners[0].members.len(), red.members.len());
+    }
+
+    #[test]
+    fn scores_add_up_over_rounds() {
+        let mut lobby = lobby_with_boards(2, &[("a", sample_board())]);
+        lobby.available_cards = (1..=25).map(|id| Card::new(id.to_string(), id)).collect();
+        let player = *lobby.boards.keys().next().unwrap();
+
+        assert!(lobby.start_new_round(None).is_err());
+
+        for round in 1..=2 {
+            lobby.correct_answers.extend([1, 2, 3, 4, 5]);
+            lobby.record_placements();
+            lobby.advance_state().unwrap();
+            assert_eq!(lobby.state, LobbyState::Completed);
+
+            assert_eq!(lobby.scores.len(), 1);
+            assert_eq!(lobby.scores[0].points, 2 * round);
+            assert_eq!(lobby.scores[0].wins, round);
+
+            assert_eq!(lobby.start_new_round(None).unwrap(), round + 1);
+            assert!(lobby.boards.is_empty());
+            assert!(lobby.correct_answers.is_empty());
+            assert!(lobby.placements.is_empty());
+            assert!(lobby.players.contains_key(&player));
+
+            // Back into the game with the same board
+            lobby.state = LobbyState::InProgress;
+            lobby.boards.insert(player, sample_board().to_vec());
+        }
+    }
+
+    #[test]
+    fn stages_with_deadlines_set_one() {
+        let mut lobby = lobby_with_boards(1, &[]);
+        lobby.state = LobbyState::WaitingForPlayers;
+        lobby.settings.deadlines = StageDeadlines {
+            crafting: Some(180),
+            game: None,
+        };
+        assert!(lobby.settings.validate().is_ok());
+
+        lobby.advance_state().unwrap();
+        let deadline = lobby.deadline.expect("crafting has a deadline");
+        assert!(deadline > Utc::now() + chrono::Duration::seconds(170));
+
+        lobby.advance_state().unwrap();
+        assert_eq!(lobby.deadline, None);
+
+        lobby.settings.deadlines.game = Some(0);
+        assert!(lobby.settings.validate().is_err());
+    }
+
+    #[test]
+    fn lobbies_expire_after_their_ttl() {
+        let mut lobby = lobby_with_boards(1, &[]);
+        let idle_ttl = chrono::Duration::minutes(30);
+        let completed_ttl = chrono::Duration::minutes(10);
+        let later = |minutes| lobby_last_activity_plus(&lobby, minutes);
+
+        assert_eq!(lobby.expiry(later(5), idle_ttl, completed_ttl), None);
+        assert_eq!(lobby.expiry(later(15), idle_ttl, completed_ttl), None);
+        assert_eq!(
+            lobby.expiry(later(30), idle_ttl, completed_ttl),
+            Some(CloseReason::Idle)
+        );
+
+        // A long game is kept until its deadline has passed
+        let deadline = later(60);
+        lobby.deadline = Some(deadline);
+        let later = |minutes| lobby_last_activity_plus(&lobby, minutes);
+        assert_eq!(lobby.expiry(later(45), idle_ttl, completed_ttl), None);
+        assert_eq!(
+            lobby.expiry(deadline, idle_ttl, completed_ttl),
+            Some(CloseReason::Idle)
+        );
+
+        lobby.complete();
+        let later = |minutes| lobby_last_activity_plus(&lobby, minutes);
+        assert_eq!(
+            lobby.expiry(later(15), idle_ttl, completed_ttl),
+            Some(CloseReason::Completed)
+        );
+    }
+
+    fn lobby_last_activity_plus(lobby: &Lobby, minutes: i64) -> DateTime<Utc> {
+        lobby.last_activity + chrono::Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn host_resumes_with_their_token() {
+        let mut lobby = lobby_with_boards(1, &[]);
+        lobby.disconnect_host();
+        assert!(lobby.host_disconnected_at.is_some());
+
+        let new_socket = Sid::new();
+        assert!(lobby.resume_host(new_socket, &ResumeToken::new()).is_err());
+        assert!(!lobby.is_host(new_socket));
+
+        let token = lobby.host_token.clone();
+        assert!(lobby.resume_host(new_socket, &token).is_ok());
+        assert!(lobby.is_host(new_socket));
+        assert_eq!(lobby.host_disconnected_at, None);
+
+        // Tokens end up in spans through the requests carrying them
+        assert_eq!(format!("{token:?}"), "ResumeToken(..)");
+    }
+
+    #[test]
+    fn player_resumes_with_their_board_and_team() {
+        let mut lobby = lobby_with_boards(1, &[("player", sample_board())]);
+        lobby.settings.teams = true;
+        let previous = *lobby.players.keys().next().unwrap();
+        lobby.teams.push(Team::new("team".into(), previous));
+
+        let (_, disconnected_at) = lobby.disconnect_player(&previous).unwrap();
+        assert_eq!(
+            lobby.players[&previous].disconnected_at,
+            Some(disconnected_at)
+        );
+
+        let new_socket = Sid::new();
+        assert!(
+            lobby
+                .resume_player(new_socket, &ResumeToken::new())
+                .is_err()
+        );
+
+        let token = lobby.players[&previous].token.clone();
+        let player = lobby.resume_player(new_socket, &token).unwrap();
+        assert_eq!(player.name, "player");
+        assert_eq!(player.disconnected_at, None);
+
+        assert!(!lobby.players.contains_key(&previous));
+        assert_eq!(lobby.boards[&new_socket], sample_board());
+        assert_eq!(lobby.team_of(&new_socket).unwrap().name, "team");
+    }
+
+    #[test]
+    fn roles_and_submitted_boards() {
+        let mut lobby = lobby_with_boards(1, &[("b", sample_board()), ("a", sample_board())]);
+        let player = Sid::new();
+        lobby.add_player(Player::new(player, "c".into()));
+
+        assert_eq!(lobby.role_of(lobby.host.id), Some(Role::Host));
+        assert_eq!(lobby.role_of(player), Some(Role::Player));
+        assert_eq!(lobby.role_of(Sid::new()), None);
+        assert_eq!(lobby.submitted_boards(), ["a", "b"]);
+    }
+
+    #[test]
+    fn event_log_replays_missed_events() {
+        let mut log = EventLog::default();
+        let (me, other) = (Sid::new(), Sid::new());
+
+        log.record("a", 1.into(), None);
+        log.record("b", 2.into(), Some(other));
+        log.record("c", 3.into(), Some(me));
+
+        let (events, complete) = log.since(1, me);
+        assert!(complete);
+        let names: Vec<&str> = events.iter().map(|logged| logged.event.as_str()).collect();
+        assert_eq!(names, ["c"]);
+
+        for _ in 0..MAX_LOGGED_EVENTS {
+            log.record("filler", 0.into(), None);
+        }
+
+        let (events, complete) = log.since(0, me);
+        assert!(!complete);
+        assert_eq!(events.len(), MAX_LOGGED_EVENTS);
+        assert_eq!(events[0].meta.seq, 4);
+
+        let (events, complete) = log.since(u64::MAX, me);
+        assert!(events.is_empty());
+        assert!(complete);
+    }
+
+    #[test]
+    fn host_moves_to_a_player() {
+        let mut lobby = lobby_with_boards(1, &[("b", sample_board()), ("a", sample_board())]);
+        let previous = lobby.host.id;
+        let host_token = lobby.host_token.clone();
+
+        let candidate = lobby.promotion_candidate().unwrap();
+        assert_eq!(lobby.players[&candidate].name, "a");
+        let player_token = lobby.players[&candidate].token.clone();
+
+        lobby.transfer_host(candidate, true).unwrap();
+        assert!(lobby.is_host(candidate));
+        assert_eq!(lobby.host.name, "a");
+        assert_eq!(lobby.host_token, player_token);
+        assert!(!lobby.boards.contains_key(&candidate));
+
+        let demoted = &lobby.players[&previous];
+        assert_eq!(demoted.name, "host");
+        assert_eq!(demoted.token, host_token);
+        assert!(!lobby.boards.contains_key(&previous));
+
+        assert!(matches!(
+            lobby.transfer_host(Sid::new(), true),
+            Err(TransferHostError::NotAPlayer)
+        ));
+
+        lobby.disconnect_player(&previous).unwrap();
+        assert!(matches!(
+            lobby.transfer_host(previous, false),
+            Err(TransferHostError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn lobby_survives_a_round_trip_through_json() {
+        let mut lobby = lobby_with_boards(1, &[("player", sample_board())]);
+        lobby.correct_answers.push(1);
+        lobby.events.record("answerSubmitted", 1.into(), None);
+
+        let json = serde_json::to_string(&lobby).unwrap();
+        let restored: Lobby = serde_json::from_str(&json).unwrap();
+
+        let (sid, player) = lobby.players.iter().next().unwrap();
+        assert_eq!(restored.players[sid].token, player.token);
+        assert_eq!(restored.boards[sid], sample_board());
+        assert_eq!(restored.host_token, lobby.host_token);
+        assert_eq!(restored.correct_answers, [1]);
+        assert_eq!(restored.state, LobbyState::InProgress);
+
+        // The events themselves are gone, so older clients have to fetch the whole state
+        assert_eq!(restored.events.last_seq(), 1);
+        assert!(!restored.events.since(0, *sid).1);
+        assert!(restored.events.since(1, *sid).1);
+    }
+
+    #[test]
+    fn spectators_watch_without_playing() {
+        let mut lobby = lobby_with_boards(1, &[("player", sample_board())]);
+        let spectator = Sid::new();
+        lobby.spectators.insert(spectator);
+
+        assert_eq!(lobby.role_of(spectator), Some(Role::Spectator));
+        assert_eq!(lobby.submitted_boards(), ["player"]);
+        assert!(matches!(
+            lobby.submit_board(spectator, sample_board().to_vec()),
+            Err(SubmitBoardError::NotCrafting | SubmitBoardError::NotAPlayer)
+        ));
+
+        for answer in [1, 2, 3, 4, 5] {
+            lobby.correct_answers.push(answer);
+            lobby.record_placements();
+        }
+        assert_eq!(lobby.placements.len(), 1);
+        assert_eq!(lobby.placements[0].winner.name, "player");
+
+        let boards = lobby.boards_by_name();
+        assert_eq!(boards.len(), 1);
+        assert_eq!(boards["player"], sample_board());
+
+        // Spectators may still take a seat
+        lobby.add_player(Player::new(spectator, "late".into()));
+        assert_eq!(lobby.role_of(spectator), Some(Role::Player));
+        assert!(lobby.spectators.is_empty());
+    }
+
+    #[test]
+    fn banned_players_stay_out() {
+        let mut lobby =
+            lobby_with_boards(1, &[("troll", sample_board()), ("other", sample_board())]);
+        lobby.state = LobbyState::WaitingForPlayers;
+        assert_eq!(lobby.player_id_by_name("nobody"), None);
+
+        // A kick only removes the player, who may come back
+        let other = lobby.player_id_by_name("other").unwrap();
+        let kicked = lobby.kick_player(&other, false).unwrap();
+        assert!(!lobby.players.contains_key(&other));
+        assert!(!lobby.boards.contains_key(&other));
+        assert!(!lobby.banned_names.contains(&kicked.name));
+        assert!(!lobby.banned_tokens.contains(&kicked.token));
+        assert!(!lobby.banned_sockets.contains(&other));
+        assert_eq!(lobby.check_join(other, "other"), Ok(()));
+
+        let troll = lobby.player_id_by_name("troll").unwrap();
+        let banned = lobby.kick_player(&troll, true).unwrap();
+        assert!(lobby.banned_sockets.contains(&troll));
+        assert_eq!(lobby.check_join(troll, "troll"), Err(JoinError::Banned));
+        assert_eq!(
+            lobby.check_join(troll, "not a troll"),
+            Err(JoinError::Banned)
+        );
+        assert!(lobby.banned_names.contains("troll"));
+        assert!(lobby.banned_tokens.contains(&banned.token));
+        assert!(lobby.resume_player(Sid::new(), &banned.token).is_err());
+
+        assert!(lobby.kick_player(&troll, true).is_none());
+    }
+
+    #[test]
+    fn private_lobbies_need_the_password_or_invite() {
+        let [right, wrong, prefix] = ["hunter2", "hunter3", "hunter"].map(Secret::from);
+        let mut lobby = lobby_with_boards(1, &[]);
+        assert!(lobby.admits(None, None));
+        assert!(lobby.admits(Some(&wrong), None));
+
+        lobby.password = Some(right.clone());
+        assert!(lobby.admits(Some(&right), None));
+        assert!(!lobby.admits(Some(&wrong), None));
+        assert!(!lobby.admits(Some(&prefix), None));
+        assert!(!lobby.admits(None, Some(&right)));
+        assert!(!lobby.admits(None, None));
+
+        let invite = Secret::generate();
+        lobby.invite_secret = Some(invite.clone());
+        assert!(lobby.admits(None, Some(&invite)));
+        assert!(lobby.admits(Some(&right), Some(&wrong)));
+        assert!(!lobby.admits(Some(&invite), None));
+
+        assert_eq!(format!("{:?}", lobby.password), "Some(Secret(..))");
+    }
+
+    #[test]
+    fn full_lobbies_keep_a_waitlist() {
+        let mut lobby = lobby_with_boards(1, &[("first", sample_board())]);
+        assert!(!lobby.is_full());
+
+        lobby.settings.max_players = Some(1);
+        assert!(lobby.is_full());
+
+        assert_eq!(lobby.join_waitlist(Sid::new(), "second".into()), 1);
+        assert_eq!(lobby.join_waitlist(Sid::new(), "third".into()), 2);
+        assert!(lobby.name_taken("host"));
+        assert!(lobby.name_taken("first"));
+        assert!(lobby.name_taken("third"));
+        assert!(!lobby.name_taken("fourth"));
+
+        let no_seats = LobbySettings {
+            max_players: Some(0),
+            ..LobbySettings::default()
+        };
+        assert!(matches!(no_seats.validate(), Err(InvalidSettings::NoSeats)));
+    }
+
+    #[test]
+    fn disconnected_sockets_leave_the_waitlist() {
+        let mut lobby = lobby_with_boards(1, &[("first", sample_board())]);
+        lobby.settings.max_players = Some(1);
+
+        let (second, third) = (Sid::new(), Sid::new());
+        lobby.join_waitlist(second, "second".into());
+        lobby.join_waitlist(third, "third".into());
+
+        assert!(lobby.leave_waitlist(&second));
+        assert!(!lobby.leave_waitlist(&second));
+        assert!(!lobby.name_taken("second"));
+        assert_eq!(
+            lobby.waitlist,
+            [WaitlistEntry {
+                id: third,
+                name: "third".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn sockets_join_only_once() {
+        let mut lobby = lobby_with_boards(1, &[("first", sample_board())]);
+        lobby.state = LobbyState::WaitingForPlayers;
+        let host = lobby.host.id;
+        let first = lobby.player_id_by_name("first").unwrap();
+        assert_eq!(
+            lobby.check_join(host, "again"),
+            Err(JoinError::AlreadyInLobby)
+        );
+        assert_eq!(
+            lobby.check_join(first, "again"),
+            Err(JoinError::AlreadyInLobby)
+        );
+
+        // A waiting socket is seated by the waitlist, not by joining directly
+        lobby.settings.max_players = Some(1);
+        let waiting = Sid::new();
+        lobby.join_waitlist(waiting, "second".into());
+        lobby.settings.max_players = None;
+        assert_eq!(
+            lobby.check_join(waiting, "third"),
+            Err(JoinError::AlreadyInLobby)
+        );
+
+        let spectator = Sid::new();
+        lobby.spectators.insert(spectator);
+        assert_eq!(lobby.check_join(spectator, "third"), Ok(()));
+        assert_eq!(
+            lobby.check_join(spectator, "second"),
+            Err(JoinError::NameTaken)
+        );
+
+        lobby.banned_names.insert("troll".into());
+        assert_eq!(
+            lobby.check_join(Sid::new(), "troll"),
+            Err(JoinError::Banned)
+        );
+
+        lobby.state = LobbyState::InProgress;
+        assert_eq!(
+            lobby.check_join(spectator, "third"),
+            Err(JoinError::NotWaiting)
+        );
+    }
+
+    #[test]
+    fn numbered_events_need_saving() {
+        let mut lobby = lobby_with_boards(1, &[]);
+        assert!(!lobby.take_unsaved());
+
+        // A reminder changes nothing but the sequence number, which must not be reused
+        lobby.events.record("stageDeadline", 30.into(), None);
+        assert!(lobby.take_unsaved());
+        assert!(!lobby.take_unsaved());
+
+        lobby.mark_changed();
+        assert!(lobby.take_unsaved());
+        assert!(!lobby.take_unsaved());
     }
 }
@@ -1,17 +1,251 @@
+use std::collections::HashMap;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
 use serde::Serialize;
+use socketioxide::socket::Sid;
 
-use crate::model::Card;
+use crate::{
+    history::HistoryEntry,
+    model::{
+        Card,
+        CardId,
+        CloseReason,
+        Lobby,
+        LobbySettings,
+        LobbyState,
+        LoggedEvent,
+        NearWin,
+        Placement,
+        ResumeToken,
+        Role,
+        Score,
+        Secret,
+        TeamSummary,
+    },
+};
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct JoinLobbyAck {
+    /// Lets the player take their seat back with `resumeSession` after reconnecting.
+    pub resume_token: ResumeToken,
     pub players: Vec<String>,
+    pub teams: Vec<TeamSummary>,
     pub host: String,
-    pub cards: [Card; 25],
+    #[serde(flatten)]
+    pub settings: LobbySettings,
+    pub cards: Vec<Card>,
+}
+
+impl JoinLobbyAck {
+    pub fn new(lobby: &Lobby, resume_token: ResumeToken) -> Self {
+        Self {
+            resume_token,
+            players: lobby
+                .players
+                .values()
+                .map(|player| player.name.clone())
+                .collect(),
+            teams: lobby.team_summaries(),
+            host: lobby.host.name.clone(),
+            settings: lobby.settings,
+            cards: lobby.available_cards.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitlistAck {
+    /// 1-based, the first socket gets the next free seat.
+    pub position: usize,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct HostLobbyAck {
     pub lobby_id: String,
-    pub cards: [Card; 25],
+
+    /// Lets the host take the lobby back with `resumeHost` after reconnecting.
+    pub resume_token: ResumeToken,
+
+    /// Only if the host asked for one, to be shared with the players.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_secret: Option<Secret>,
+    #[serde(flatten)]
+    pub settings: LobbySettings,
+    pub cards: Vec<Card>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Standings<'a> {
+    pub round: u32,
+    pub placements: &'a [Placement],
+
+    /// Cumulative over every round played in the lobby.
+    pub scores: &'a [Score],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewRound<'a> {
+    pub round: u32,
+    pub cards: &'a [Card],
+    pub scores: &'a [Score],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnswerRetracted<'a> {
+    pub card_id: CardId,
+
+    /// Players who no longer have a placement.
+    pub revoked: Vec<String>,
+    pub placements: &'a [Placement],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnswerSubmitAck {
+    /// The card had already been submitted, nothing changed.
+    pub already_submitted: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageDeadline {
+    /// The stage that ends at the deadline.
+    pub state: LobbyState,
+    pub deadline: DateTime<Utc>,
+    pub remaining_seconds: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LobbyClosed {
+    pub reason: CloseReason,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostDisconnected {
+    /// The lobby closes if the host doesn't come back within this many seconds.
+    pub grace_seconds: u64,
+}
+
+/// Everything a client needs to rebuild its view of a lobby.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LobbySnapshot<'a> {
+    /// The snapshot includes every event up to this sequence number.
+    pub seq: u64,
+    pub state: LobbyState,
+    pub round: u32,
+    #[serde(flatten)]
+    pub settings: LobbySettings,
+    pub host: &'a str,
+    pub cards: &'a [Card],
+    pub players: Vec<String>,
+    pub teams: Vec<TeamSummary>,
+    pub correct_answers: &'a [CardId],
+    pub placements: &'a [Placement],
+
+    /// The names of the players who have a board.
+    pub submitted_boards: Vec<String>,
+
+    pub role: Role,
+
+    /// The caller's own board, if they have one yet.
+    pub board: Option<&'a [CardId]>,
+
+    /// Empty unless the lobby's settings let the caller see near wins.
+    pub near_wins: Vec<NearWin>,
+
+    /// Every player's board by name, for spectators once the game is in progress if the lobby's
+    /// settings allow it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boards: Option<HashMap<String, &'a [CardId]>>,
+}
+
+impl<'a> LobbySnapshot<'a> {
+    /// The lobby as seen by the socket `sid` in `role`.
+    pub fn new(lobby: &'a Lobby, sid: Sid, role: Role) -> Self {
+        let near_wins =
+            if lobby.state == LobbyState::InProgress && lobby.settings.near_wins.visible_to(role) {
+                lobby.near_wins()
+            } else {
+                Vec::new()
+            };
+
+        let boards = (role == Role::Spectator
+            && lobby.settings.spectator_boards
+            && matches!(lobby.state, LobbyState::InProgress | LobbyState::Completed))
+        .then(|| lobby.boards_by_name());
+
+        Self {
+            seq: lobby.events.last_seq(),
+            state: lobby.state,
+            round: lobby.round,
+            settings: lobby.settings,
+            host: &lobby.host.name,
+            cards: &lobby.available_cards,
+            players: lobby
+                .players
+                .values()
+                .map(|player| player.name.clone())
+                .collect(),
+            teams: lobby.team_summaries(),
+            correct_answers: &lobby.correct_answers,
+            placements: &lobby.placements,
+            submitted_boards: lobby.submitted_boards(),
+            role,
+            board: lobby.boards.get(&sid).map(Vec::as_slice),
+            near_wins,
+            boards,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayedEvents<'a> {
+    pub events: Vec<&'a LoggedEvent>,
+
+    /// False if some of the missed events are no longer logged. The client should fetch the
+    /// lobby state with `getLobbyState` instead.
+    pub complete: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostChanged<'a> {
+    pub host: &'a str,
+    pub previous: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayStep<'a> {
+    pub step: usize,
+
+    /// How many entries the history has in total.
+    pub steps: usize,
+    pub entry: &'a HistoryEntry,
+
+    /// The lobby right after the entry, always with every board handed out so far.
+    #[serde(flatten)]
+    pub lobby: LobbySnapshot<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerKicked {
+    pub name: String,
+
+    /// The player can't join the lobby again.
+    pub banned: bool,
 }
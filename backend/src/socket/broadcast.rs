@@ -0,0 +1,79 @@
+use serde::Serialize;
+use socketioxide::{
+    SocketIo,
+    socket::Sid,
+};
+use tracing::error;
+
+use crate::{
+    model::{
+        EventLog,
+        EventMeta,
+        LobbyId,
+    },
+    socket::events::ServerEvent,
+};
+
+/// Emits `event` to everyone in the lobby. The event is numbered and logged so clients that miss
+/// it can replay it later.
+pub async fn to_lobby(
+    io: &SocketIo,
+    lobby_id: LobbyId,
+    events: &mut EventLog,
+    event: ServerEvent,
+    data: &impl Serialize,
+) {
+    let Some(payload) = record(events, event, data, None) else {
+        return;
+    };
+
+    if let Err(err) = io.to(lobby_id.to_string()).emit(event, &payload).await {
+        error!(
+            "Failed to emit {} to lobby {}: {}",
+            event.as_ref(),
+            lobby_id,
+            err
+        );
+    }
+}
+
+/// Emits `event` to the socket `sid` only, numbered and logged like [`to_lobby`].
+pub async fn to_socket(
+    io: &SocketIo,
+    sid: Sid,
+    events: &mut EventLog,
+    event: ServerEvent,
+    data: &impl Serialize,
+) {
+    let Some(payload) = record(events, event, data, Some(sid)) else {
+        return;
+    };
+
+    if let Err(err) = io.to(sid).emit(event, &payload).await {
+        error!(
+            "Failed to emit {} to socket {}: {}",
+            event.as_ref(),
+            sid,
+            err
+        );
+    }
+}
+
+/// Logs the event and returns the emitted arguments: the data, followed by its metadata.
+fn record(
+    events: &mut EventLog,
+    event: ServerEvent,
+    data: &impl Serialize,
+    recipient: Option<Sid>,
+) -> Option<(serde_json::Value, EventMeta)> {
+    let data = match serde_json::to_value(data) {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Failed to serialize {}: {}", event.as_ref(), err);
+            return None;
+        }
+    };
+
+    let meta = events.record(event.as_ref(), data.clone(), recipient);
+    Some((data, meta))
+}
@@ -0,0 +1,164 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::{
+    history::ReplayError,
+    model::{
+        InvalidBoard,
+        InvalidCardPool,
+        InvalidResumeToken,
+        InvalidSettings,
+        JoinError,
+        RetractAnswerError,
+        RoundNotCompleted,
+        SubmitAnswerError,
+        SubmitBoardError,
+        TeamError,
+        TransferHostError,
+    },
+};
+
+/// A machine-readable reason sent along with every failure acknowledgement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    LobbyNotFound,
+    NotHost,
+    NotAPlayer,
+
+    /// The request is not allowed in the lobby's current state.
+    WrongStage,
+    NameTaken,
+    InvalidSettings,
+    InvalidCardPool,
+    InvalidBoard,
+    UnknownCard,
+    DuplicateCard,
+    AnswerNotSubmitted,
+    TeamsDisabled,
+    InvalidTeamName,
+    InvalidResumeToken,
+    PlayerDisconnected,
+    ReplayFailed,
+    AlreadyInLobby,
+
+    /// The host banned the player from the lobby.
+    Banned,
+    WrongPassword,
+
+    /// The socket sent too many wrong passwords and has to wait.
+    TooManyAttempts,
+
+    /// Every seat is taken, the socket may join the waitlist instead.
+    LobbyFull,
+
+    /// The waitlist is only for full lobbies.
+    SeatsAvailable,
+}
+
+/// An error that can be sent to the client as a failure acknowledgement.
+pub trait ErrorCoded: Display {
+    fn code(&self) -> ErrorCode;
+}
+
+impl ErrorCoded for InvalidSettings {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::InvalidSettings
+    }
+}
+
+impl ErrorCoded for InvalidCardPool {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::InvalidCardPool
+    }
+}
+
+impl ErrorCoded for RoundNotCompleted {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::WrongStage
+    }
+}
+
+impl ErrorCoded for InvalidBoard {
+    fn code(&self) -> ErrorCode {
+        match self {
+            InvalidBoard::WrongLength { .. } => ErrorCode::InvalidBoard,
+            InvalidBoard::UnknownCard(_) => ErrorCode::UnknownCard,
+            InvalidBoard::DuplicateCard(_) => ErrorCode::DuplicateCard,
+        }
+    }
+}
+
+impl ErrorCoded for SubmitBoardError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            SubmitBoardError::NotCrafting => ErrorCode::WrongStage,
+            SubmitBoardError::NotAPlayer => ErrorCode::NotAPlayer,
+            SubmitBoardError::InvalidBoard(err) => err.code(),
+        }
+    }
+}
+
+impl ErrorCoded for SubmitAnswerError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            SubmitAnswerError::NotInProgress => ErrorCode::WrongStage,
+            SubmitAnswerError::UnknownCard(_) => ErrorCode::UnknownCard,
+        }
+    }
+}
+
+impl ErrorCoded for TeamError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            TeamError::TeamsDisabled => ErrorCode::TeamsDisabled,
+            TeamError::NotWaiting => ErrorCode::WrongStage,
+            TeamError::NotAPlayer => ErrorCode::NotAPlayer,
+            TeamError::InvalidName => ErrorCode::InvalidTeamName,
+        }
+    }
+}
+
+impl ErrorCoded for InvalidResumeToken {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::InvalidResumeToken
+    }
+}
+
+impl ErrorCoded for TransferHostError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            TransferHostError::NotAPlayer => ErrorCode::NotAPlayer,
+            TransferHostError::Disconnected => ErrorCode::PlayerDisconnected,
+        }
+    }
+}
+
+impl ErrorCoded for ReplayError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::ReplayFailed
+    }
+}
+
+impl ErrorCoded for RetractAnswerError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            RetractAnswerError::WrongStage | RetractAnswerError::NoPlaceRevoked => {
+                ErrorCode::WrongStage
+            }
+            RetractAnswerError::NotSubmitted => ErrorCode::AnswerNotSubmitted,
+        }
+    }
+}
+
+impl ErrorCoded for JoinError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            JoinError::AlreadyInLobby => ErrorCode::AlreadyInLobby,
+            JoinError::NotWaiting => ErrorCode::WrongStage,
+            JoinError::Banned => ErrorCode::Banned,
+            JoinError::NameTaken => ErrorCode::NameTaken,
+        }
+    }
+}
@@ -5,9 +5,25 @@ use identconv::camel_strify;
 client_events! {
     HostLobby,
     JoinLobby,
+    SpectateLobby,
     TriggerNextStage,
     SubmitBoard,
     SubmitAnswer,
+    RetractAnswer,
+    GenerateBoard,
+    JoinTeam,
+    AssignTeam,
+    StartNewRound,
+    ResumeHost,
+    ResumeSession,
+    GetLobbyState,
+    ReplayEvents,
+    TransferHost,
+    ExportHistory,
+    ReplayGame,
+    KickPlayer,
+    BanPlayer,
+    JoinWaitlist,
 }
 
 server_events! {
@@ -18,6 +34,21 @@ server_events! {
     BoardSubmitted,
     AnswerSubmitted,
     WinnerDetected,
+    GameCompleted,
+    AnswerRetracted,
+    NearWin,
+    BoardAssigned,
+    TeamsChanged,
+    NewRound,
+    StageDeadline,
+    HostDisconnected,
+    HostReconnected,
+    PlayerDisconnected,
+    PlayerReconnected,
+    LobbySnapshot,
+    HostChanged,
+    PlayerKicked,
+    PromotedFromWaitlist,
 }
 
 macro_rules! client_events {
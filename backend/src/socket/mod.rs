@@ -1,9 +1,19 @@
 pub mod acks;
+pub mod broadcast;
+pub mod errors;
 pub mod events;
 pub mod request;
+pub mod stage;
+pub mod sweeper;
+pub mod throttle;
+pub mod waitlist;
 
-use std::str::FromStr;
+use std::{
+    str::FromStr,
+    time::Instant,
+};
 
+use axum_extra::extract::CookieJar;
 use monostate::{
     MustBe,
     MustBeBool,
@@ -25,40 +35,91 @@ use tracing::{
 };
 
 use crate::{
+    config::Config,
+    history::{
+        GameEvent,
+        ReplayError,
+    },
     model::{
-        Card,
         Host,
+        InvalidResumeToken,
+        Lobby,
         LobbyId,
         LobbyManager,
         LobbyState,
+        NearWinVisibility,
         Player,
+        ResumeToken,
+        Role,
+        Secret,
     },
     socket::{
         acks::{
+            AnswerRetracted,
+            AnswerSubmitAck,
+            HostDisconnected,
             HostLobbyAck,
             JoinLobbyAck,
+            LobbySnapshot,
+            NewRound,
+            PlayerKicked,
+            ReplayStep,
+            ReplayedEvents,
+            WaitlistAck,
+        },
+        errors::{
+            ErrorCode,
+            ErrorCoded,
         },
         events::{
             ClientEvent,
             ServerEvent,
         },
         request::{
+            AnswerRetractRequest,
             AnswerSubmitRequest,
+            AssignTeamRequest,
             BoardSubmitRequest,
+            GenerateBoardRequest,
             HostLobbyRequest,
             JoinLobbyRequest,
+            JoinTeamRequest,
+            KickPlayerRequest,
+            LobbyAccess,
+            NewRoundRequest,
+            ReplayEventsRequest,
+            ReplayGameRequest,
+            ResumeHostRequest,
+            ResumeSessionRequest,
+            SpectateLobbyRequest,
+            TransferHostRequest,
         },
+        throttle::FailedJoins,
+        waitlist::Waitlisted,
     },
 };
 
+/// The cookie clients keep their player resume token for `lobby_id` in. There is one per lobby,
+/// so joining another lobby doesn't replace it.
+fn resume_cookie(lobby_id: LobbyId) -> String {
+    format!("wfb_resume_token_{lobby_id}")
+}
+
 type True = MustBe!(true);
 type False = MustBe!(false);
 
 #[derive(Debug, Serialize)]
 #[serde(untagged, rename_all = "camelCase")]
 pub enum Acknowledgement<T = ()> {
-    Success { success: True, data: T },
-    Failure { success: False, message: String },
+    Success {
+        success: True,
+        data: T,
+    },
+    Failure {
+        success: False,
+        code: ErrorCode,
+        message: String,
+    },
 }
 
 impl Acknowledgement {
@@ -69,19 +130,25 @@ impl Acknowledgement {
         }
     }
 
-    pub fn failure(message: impl Into<String>) -> Acknowledgement<()> {
+    pub fn failure(code: ErrorCode, message: impl Into<String>) -> Acknowledgement<()> {
         Acknowledgement::<()>::Failure {
             success: MustBeBool,
+            code,
             message: message.into(),
         }
     }
 
-    pub fn failure_t<T>(message: impl Into<String>) -> Acknowledgement<T> {
+    pub fn failure_t<T>(code: ErrorCode, message: impl Into<String>) -> Acknowledgement<T> {
         Acknowledgement::<T>::Failure {
             success: MustBeBool,
+            code,
             message: message.into(),
         }
     }
+
+    pub fn rejected(err: &impl ErrorCoded) -> Acknowledgement<()> {
+        Self::failure(err.code(), err.to_string())
+    }
 }
 
 #[instrument(name = "socket.connect", skip(socket))]
@@ -90,18 +157,55 @@ pub async fn on_connect(socket: SocketRef) {
 
     socket.on(ClientEvent::HostLobby, host_lobby);
     socket.on(ClientEvent::JoinLobby, join_lobby);
+    socket.on(ClientEvent::JoinWaitlist, join_waitlist);
+    socket.on(ClientEvent::SpectateLobby, spectate_lobby);
     socket.on(ClientEvent::TriggerNextStage, trigger_next_stage);
     socket.on(ClientEvent::SubmitBoard, submit_board);
     socket.on(ClientEvent::SubmitAnswer, submit_answer);
+    socket.on(ClientEvent::RetractAnswer, retract_answer);
+    socket.on(ClientEvent::GenerateBoard, generate_board);
+    socket.on(ClientEvent::JoinTeam, join_team);
+    socket.on(ClientEvent::AssignTeam, assign_team);
+    socket.on(ClientEvent::StartNewRound, start_new_round);
+    socket.on(ClientEvent::ResumeHost, resume_host);
+    socket.on(ClientEvent::ResumeSession, resume_session);
+    socket.on(ClientEvent::GetLobbyState, get_lobby_state);
+    socket.on(ClientEvent::ReplayEvents, replay_events);
+    socket.on(ClientEvent::TransferHost, transfer_host);
+    socket.on(ClientEvent::ExportHistory, export_history);
+    socket.on(ClientEvent::ReplayGame, replay_game);
+    socket.on(
+        ClientEvent::KickPlayer,
+        async |socket, io, data, state, ack| kick_player(socket, io, data, state, ack, false).await,
+    );
+    socket.on(
+        ClientEvent::BanPlayer,
+        async |socket, io, data, state, ack| kick_player(socket, io, data, state, ack, true).await,
+    );
 
     socket.on_disconnect(on_disconnect);
 }
 
-#[instrument(name = "socket.disconnect", skip(socket, io, manager))]
-async fn on_disconnect(socket: SocketRef, io: SocketIo, State(manager): State<LobbyManager>) {
+#[instrument(name = "socket.disconnect", skip(socket, io, manager, config))]
+async fn on_disconnect(
+    socket: SocketRef,
+    io: SocketIo,
+    State(manager): State<LobbyManager>,
+    State(config): State<Config>,
+) {
     info!("Socket disconnected: {}", socket.id);
 
-    let mut lobbies = manager.lobbies.lock().await;
+    let mut lobbies = manager.lock().await;
+
+    if let Some(Waitlisted(lobby_ids)) = socket.extensions.get::<Waitlisted>() {
+        for lobby_id in lobby_ids {
+            if let Some(lobby) = lobbies.get_mut(&lobby_id)
+                && lobby.leave_waitlist(&socket.id)
+            {
+                info!(%lobby_id, "socket {} left the waitlist", socket.id);
+            }
+        }
+    }
 
     let rooms = socket.rooms();
 
@@ -113,29 +217,66 @@ async fn on_disconnect(socket: SocketRef, io: SocketIo, State(manager): State<Lo
         if let Some(ref mut lobby) = lobbies.get_mut(&lobby_id) {
             info!("Socket {} was in lobby {}", socket.id, lobby_id);
 
+            // Spectators have no seat to keep
+            if lobby.spectators.remove(&socket.id) {
+                continue;
+            }
+
             if lobby.host.id == socket.id {
-                lobbies.remove(&lobby_id);
-                info!(%lobby_id, lobby_count = lobbies.len(), "deleted lobby");
-
-                io.within(room.clone())
-                    .leave(room)
-                    .await
-                    .expect("Failed to disconnect");
-            } else if let Some(player) = lobby.remove_player(&socket.id) {
-                io.to(room)
-                    .emit(ServerEvent::UserLeft, &player.name)
-                    .await
-                    .ok();
+                let disconnected_at = lobby.disconnect_host();
+                lobby.mark_changed();
+                info!(%lobby_id, "host disconnected, waiting for them to come back");
+
+                broadcast::to_lobby(
+                    &io,
+                    lobby_id,
+                    &mut lobby.events,
+                    ServerEvent::HostDisconnected,
+                    &HostDisconnected {
+                        grace_seconds: config.host_grace_period.as_secs(),
+                    },
+                )
+                .await;
+
+                sweeper::schedule_host_timeout(
+                    io.clone(),
+                    manager.clone(),
+                    lobby_id,
+                    disconnected_at,
+                    config.host_grace_period,
+                );
+            } else if let Some((player, disconnected_at)) = lobby.disconnect_player(&socket.id) {
+                let (name, token) = (player.name.clone(), player.token.clone());
+                lobby.mark_changed();
+
+                broadcast::to_lobby(
+                    &io,
+                    lobby_id,
+                    &mut lobby.events,
+                    ServerEvent::PlayerDisconnected,
+                    &name,
+                )
+                .await;
+
+                sweeper::schedule_player_timeout(
+                    io.clone(),
+                    manager.clone(),
+                    lobby_id,
+                    token,
+                    disconnected_at,
+                    config.player_grace_period,
+                );
             }
         }
     }
 }
 
-#[instrument(name = "lobby.host", skip(socket, manager, ack))]
+#[instrument(name = "lobby.host", skip(socket, manager, config, ack))]
 async fn host_lobby(
     socket: SocketRef,
     Data(request): Data<HostLobbyRequest>,
     State(manager): State<LobbyManager>,
+    State(config): State<Config>,
     ack: AckSender,
 ) {
     info!(
@@ -143,27 +284,47 @@ async fn host_lobby(
         "Socket {} is attempting to host a lobby ", socket.id
     );
 
+    let mut settings = request.settings;
+
+    if let Err(err) = settings.validate() {
+        error!("Socket {} sent invalid lobby settings: {}", socket.id, err);
+        let _ = ack.send(&Acknowledgement::rejected(&err));
+        return;
+    }
+
+    settings.max_players = Some(
+        settings
+            .max_players
+            .map_or(config.max_players, |max| max.min(config.max_players)),
+    );
+
+    let cards = match settings.card_pool(request.cards) {
+        Ok(cards) => cards,
+        Err(err) => {
+            error!("Socket {} sent an invalid card pool: {}", socket.id, err);
+            let _ = ack.send(&Acknowledgement::rejected(&err));
+            return;
+        }
+    };
+
     let lobby_id = LobbyId::new();
 
     let host = Host::new(socket.id, request.host_name);
 
-    let cards: [Card; 25] = request
-        .cards
-        .iter()
-        .enumerate()
-        .map(|(idx, card)| Card::new(card.clone(), idx as u8))
-        .collect::<Vec<Card>>()
-        .try_into()
-        .expect("Failed to convert cards to array");
+    let mut lobby = Lobby::new(host, settings, cards.clone());
+    lobby.password = request.password.filter(|password| !password.is_empty());
+    lobby.invite_secret = request.invite.then(Secret::generate);
+    let invite_secret = lobby.invite_secret.clone();
 
-    manager
-        .create_lobby(lobby_id, host.clone(), cards.clone())
-        .await;
+    let resume_token = manager.create_lobby(lobby_id, lobby).await;
 
     socket.join(lobby_id.to_string());
 
     match ack.send(&Acknowledgement::success(HostLobbyAck {
         lobby_id: lobby_id.to_string(),
+        resume_token,
+        invite_secret,
+        settings,
         cards,
     })) {
         Ok(_) => info!("Successfully hosted lobby {}", lobby_id),
@@ -187,56 +348,47 @@ async fn join_lobby(
         socket.id, request.lobby_id
     );
 
-    let mut lobbies = manager.lobbies.lock().await;
+    let mut lobbies = manager.lock().await;
 
     // Check if the lobby exists
     let Some(lobby) = lobbies.get_mut(&request.lobby_id) else {
         error!("Lobby {} does not exist", request.lobby_id);
-        let _ = ack.send(&Acknowledgement::failure("Lobby does not exist"));
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
         return;
     };
 
-    // Check if the lobby is accepting new players
-    if lobby.state != LobbyState::WaitingForPlayers {
-        error!("Lobby {} is not accepting new players", request.lobby_id);
-        let _ = ack.send(&Acknowledgement::failure(
-            "Lobby is not accepting new players",
-        ));
+    if let Err(failure) = check_join(&socket, lobby, &request) {
+        let _ = ack.send(&failure);
         return;
     }
 
-    // Check if the player name is already taken
-    if lobby
-        .players
-        .values()
-        .find(|player| player.name == request.player_name)
-        .is_some()
-        || lobby.host.name == request.player_name
-    {
-        error!(
-            "Player name {} is already taken in lobby {}",
-            request.player_name, request.lobby_id
-        );
-        let _ = ack.send(&Acknowledgement::failure("Player name is already taken"));
+    if lobby.is_full() {
+        info!(lobby_id = %request.lobby_id, "lobby is full, offering the waitlist");
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyFull,
+            "Lobby is full, join the waitlist to get the next free seat",
+        ));
         return;
     }
 
-    lobby.players.insert(
-        socket.id,
-        Player::new(socket.id, request.player_name.clone()),
+    let player = Player::new(socket.id, request.player_name.clone());
+    let resume_token = player.token.clone();
+    lobby.add_player(player);
+    lobby.history.record(
+        Some(&request.player_name),
+        GameEvent::Joined {
+            name: request.player_name.clone(),
+        },
     );
+    lobby.mark_changed();
 
-    let players: Vec<String> = lobby
-        .players
-        .values()
-        .map(|player| player.name.clone())
-        .collect();
-
-    match ack.send(&Acknowledgement::success(JoinLobbyAck {
-        players,
-        host: lobby.host.name.clone(),
-        cards: lobby.available_cards.clone(),
-    })) {
+    match ack.send(&Acknowledgement::success(JoinLobbyAck::new(
+        lobby,
+        resume_token,
+    ))) {
         Ok(_) => {
             socket.join(request.lobby_id.to_string());
         }
@@ -246,12 +398,163 @@ async fn join_lobby(
         }
     };
 
-    if let Err(err) = io
-        .within(request.lobby_id.to_string())
-        .emit(ServerEvent::UserJoined, &request.player_name)
-        .await
-    {
-        error!("Failed to emit user joined event: {}", err);
+    broadcast::to_lobby(
+        &io,
+        request.lobby_id,
+        &mut lobby.events,
+        ServerEvent::UserJoined,
+        &request.player_name,
+    )
+    .await;
+}
+
+/// Everything that keeps the request from joining the lobby, apart from the lobby being full.
+fn check_join(
+    socket: &SocketRef,
+    lobby: &Lobby,
+    request: &JoinLobbyRequest,
+) -> Result<(), Acknowledgement> {
+    check_access(socket, lobby, &request.access)?;
+
+    lobby
+        .check_join(socket.id, &request.player_name)
+        .map_err(|err| {
+            error!(
+                "Socket {} can't join lobby {} as {}: {}",
+                socket.id, request.lobby_id, request.player_name, err
+            );
+            Acknowledgement::rejected(&err)
+        })
+}
+
+#[instrument(name = "lobby.join_waitlist", skip(socket, manager, ack))]
+async fn join_waitlist(
+    socket: SocketRef,
+    Data(request): Data<JoinLobbyRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&request.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if let Err(failure) = check_join(&socket, lobby, &request) {
+        let _ = ack.send(&failure);
+        return;
+    }
+
+    if !lobby.is_full() {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::SeatsAvailable,
+            "The lobby has free seats, join it directly",
+        ));
+        return;
+    }
+
+    let position = lobby.join_waitlist(socket.id, request.player_name);
+
+    let mut waitlisted = socket.extensions.get::<Waitlisted>().unwrap_or_default();
+    waitlisted.0.insert(request.lobby_id);
+    socket.extensions.insert(waitlisted);
+    info!(lobby_id = %request.lobby_id, position, "socket {} joined the waitlist", socket.id);
+
+    if let Err(err) = ack.send(&Acknowledgement::success(WaitlistAck { position })) {
+        error!("Failed to send join waitlist ack: {}", err);
+    }
+}
+
+/// Checks the password or invite secret of a private lobby. Sockets that sent too many wrong ones
+/// are turned away without checking.
+fn check_access(
+    socket: &SocketRef,
+    lobby: &Lobby,
+    access: &LobbyAccess,
+) -> Result<(), Acknowledgement> {
+    let now = Instant::now();
+    let mut failed = socket.extensions.get::<FailedJoins>().unwrap_or_default();
+
+    if let Some(retry_after) = failed.retry_after(now) {
+        error!("Socket {} sent too many wrong passwords", socket.id);
+        return Err(Acknowledgement::failure(
+            ErrorCode::TooManyAttempts,
+            format!(
+                "Too many wrong passwords, try again in {} seconds",
+                retry_after.as_secs().max(1)
+            ),
+        ));
+    }
+
+    if lobby.admits(access.password.as_ref(), access.invite.as_ref()) {
+        return Ok(());
+    }
+
+    failed.record(now);
+    socket.extensions.insert(failed);
+
+    Err(Acknowledgement::failure(
+        ErrorCode::WrongPassword,
+        "Wrong password or invite",
+    ))
+}
+
+#[instrument(name = "lobby.spectate", skip(socket, manager, ack))]
+pub async fn spectate_lobby(
+    socket: SocketRef,
+    Data(request): Data<SpectateLobbyRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let lobby_id = request.lobby_id;
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if lobby.role_of(socket.id).is_some() {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::AlreadyInLobby,
+            "You are already part of this lobby",
+        ));
+        return;
+    }
+
+    // Watching is no way back in after a ban
+    if lobby.banned_sockets.contains(&socket.id) {
+        error!("Socket {} is banned from lobby {}", socket.id, lobby_id);
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::Banned,
+            "You are banned from this lobby",
+        ));
+        return;
+    }
+
+    if let Err(failure) = check_access(&socket, lobby, &request.access) {
+        let _ = ack.send(&failure);
+        return;
+    }
+
+    // Spectators may come in at any stage
+    lobby.spectators.insert(socket.id);
+    socket.join(lobby_id.to_string());
+    info!(%lobby_id, spectators = lobby.spectators.len(), "socket {} is spectating", socket.id);
+
+    if let Err(err) = ack.send(&Acknowledgement::success(LobbySnapshot::new(
+        lobby,
+        socket.id,
+        Role::Spectator,
+    ))) {
+        error!("Failed to send spectate lobby ack: {}", err);
     }
 }
 
@@ -262,7 +565,7 @@ pub async fn trigger_next_stage(
     Data(lobby_id): Data<LobbyId>,
     State(manager): State<LobbyManager>,
 ) {
-    let mut lobbies = manager.lobbies.lock().await;
+    let mut lobbies = manager.lock().await;
 
     let Some(lobby) = lobbies.get_mut(&lobby_id) else {
         return;
@@ -272,14 +575,240 @@ pub async fn trigger_next_stage(
         return;
     }
 
-    let Ok(state) = lobby.advance_state() else {
+    let host = lobby.host.name.clone();
+    if let Err(err) = stage::advance_stage(&io, &manager, lobby_id, lobby, Some(host)).await {
+        error!("Failed to advance lobby {}: {}", lobby_id, err);
+    }
+}
+
+#[instrument(name = "lobby.join_team", skip(socket, io, manager, ack))]
+pub async fn join_team(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(req): Data<JoinTeamRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if let Err(err) = lobby.set_team(socket.id, req.team.as_deref()) {
+        error!("Socket {} failed to join a team: {}", socket.id, err);
+        let _ = ack.send(&Acknowledgement::rejected(&err));
+        return;
+    }
+
+    if let Some(player) = lobby.players.get(&socket.id) {
+        let name = player.name.clone();
+        lobby.history.record(
+            Some(&name),
+            GameEvent::TeamChanged {
+                name: name.clone(),
+                team: req.team.clone(),
+            },
+        );
+    }
+    lobby.mark_changed();
+
+    match ack.send(&Acknowledgement::success(())) {
+        Ok(_) => {
+            let teams = lobby.team_summaries();
+            broadcast::to_lobby(
+                &io,
+                req.lobby_id,
+                &mut lobby.events,
+                ServerEvent::TeamsChanged,
+                &teams,
+            )
+            .await;
+        }
+        Err(err) => {
+            error!("Failed to send join team ack: {}", err);
+        }
+    };
+}
+
+#[instrument(name = "lobby.assign_team", skip(socket, io, manager, ack))]
+pub async fn assign_team(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(req): Data<AssignTeamRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if !lobby.is_host(socket.id) {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotHost,
+            "Only the host can assign teams",
+        ));
+        return;
+    }
+
+    let Some(player_id) = lobby.player_id_by_name(&req.player_name) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotAPlayer,
+            "Player does not exist",
+        ));
+        return;
+    };
+
+    if let Err(err) = lobby.set_team(player_id, req.team.as_deref()) {
+        error!("Failed to assign {} to a team: {}", req.player_name, err);
+        let _ = ack.send(&Acknowledgement::rejected(&err));
+        return;
+    }
+
+    let host = lobby.host.name.clone();
+    lobby.history.record(
+        Some(&host),
+        GameEvent::TeamChanged {
+            name: req.player_name.clone(),
+            team: req.team.clone(),
+        },
+    );
+    lobby.mark_changed();
+
+    match ack.send(&Acknowledgement::success(())) {
+        Ok(_) => {
+            let teams = lobby.team_summaries();
+            broadcast::to_lobby(
+                &io,
+                req.lobby_id,
+                &mut lobby.events,
+                ServerEvent::TeamsChanged,
+                &teams,
+            )
+            .await;
+        }
+        Err(err) => {
+            error!("Failed to send assign team ack: {}", err);
+        }
+    };
+}
+
+#[instrument(name = "lobby.generate_board", skip(socket, manager, ack))]
+pub async fn generate_board(
+    socket: SocketRef,
+    Data(req): Data<GenerateBoardRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    // The card pool of a private lobby is only for those who got in
+    if lobby.role_of(socket.id).is_none() {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotAPlayer,
+            "You are not part of this lobby",
+        ));
+        return;
+    }
+
+    if let Err(err) = ack.send(&Acknowledgement::success(lobby.generate_board(req.seed))) {
+        error!("Failed to send generate board ack: {}", err);
+    }
+}
+
+#[instrument(name = "lobby.new_round", skip(socket, io, manager, ack))]
+pub async fn start_new_round(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(req): Data<NewRoundRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if !lobby.is_host(socket.id) {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotHost,
+            "Only the host can start a new round",
+        ));
         return;
+    }
+
+    // Without new cards, the current card pool is kept
+    let cards = match req.cards.map(|cards| lobby.settings.card_pool(cards)) {
+        Some(Ok(cards)) => Some(cards),
+        Some(Err(err)) => {
+            error!("Socket {} sent an invalid card pool: {}", socket.id, err);
+            let _ = ack.send(&Acknowledgement::rejected(&err));
+            return;
+        }
+        None => None,
+    };
+
+    let round = match lobby.start_new_round(cards) {
+        Ok(round) => round,
+        Err(err) => {
+            let _ = ack.send(&Acknowledgement::rejected(&err));
+            return;
+        }
     };
 
-    io.to(lobby_id.to_string())
-        .emit(ServerEvent::NextStage, &state)
-        .await
-        .ok();
+    info!(lobby_id = %req.lobby_id, round, "started new round");
+
+    let host = lobby.host.name.clone();
+    let cards = lobby.available_cards.clone();
+    lobby
+        .history
+        .record(Some(&host), GameEvent::NewRound { cards });
+    lobby.mark_changed();
+
+    match ack.send(&Acknowledgement::success(())) {
+        Ok(_) => {
+            broadcast::to_lobby(
+                &io,
+                req.lobby_id,
+                &mut lobby.events,
+                ServerEvent::NewRound,
+                &NewRound {
+                    round,
+                    cards: &lobby.available_cards,
+                    scores: &lobby.scores,
+                },
+            )
+            .await;
+
+            stage::emit_snapshots(&io, lobby).await;
+        }
+        Err(err) => {
+            error!("Failed to send new round ack: {}", err);
+        }
+    };
 }
 
 #[instrument(name = "lobby.board_submitted", skip(socket, io, manager, ack))]
@@ -290,28 +819,45 @@ pub async fn submit_board(
     State(manager): State<LobbyManager>,
     ack: AckSender,
 ) {
-    let mut lobbies = manager.lobbies.lock().await;
+    let mut lobbies = manager.lock().await;
 
     let Some(lobby) = lobbies.get_mut(&req.lobby_id) else {
+        error!("Lobby {} does not exist", req.lobby_id);
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
         return;
     };
 
-    lobby.boards.insert(socket.id, req.cards);
+    let player_name = match lobby.submit_board(socket.id, req.cards.clone()) {
+        Ok(player) => player.name.clone(),
+        Err(err) => {
+            error!("Socket {} failed to submit a board: {}", socket.id, err);
+            let _ = ack.send(&Acknowledgement::rejected(&err));
+            return;
+        }
+    };
 
-    let player_name = lobby
-        .players
-        .values()
-        .find(|p| p.id == socket.id)
-        .unwrap()
-        .name
-        .as_str();
+    lobby.history.record(
+        Some(&player_name),
+        GameEvent::BoardSubmitted {
+            name: player_name.clone(),
+            cards: req.cards,
+        },
+    );
+    lobby.mark_changed();
 
     match ack.send(&Acknowledgement::success(())) {
         Ok(_) => {
-            io.within(req.lobby_id.to_string())
-                .emit(ServerEvent::BoardSubmitted, player_name)
-                .await
-                .ok();
+            broadcast::to_lobby(
+                &io,
+                req.lobby_id,
+                &mut lobby.events,
+                ServerEvent::BoardSubmitted,
+                &player_name,
+            )
+            .await;
         }
         Err(err) => {
             error!("Failed to send submit board ack: {}", err);
@@ -327,35 +873,691 @@ pub async fn submit_answer(
     State(manager): State<LobbyManager>,
     ack: AckSender,
 ) {
-    let mut lobbies = manager.lobbies.lock().await;
+    let mut lobbies = manager.lock().await;
 
     let Some(lobby) = lobbies.get_mut(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
         return;
     };
 
     if !lobby.is_host(socket.id) {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotHost,
+            "Only the host can submit answers",
+        ));
         return;
     }
 
-    lobby.correct_answers.push(req.card_id);
-    let winners = lobby.check_winners();
-
-    match ack.send(&Acknowledgement::success(())) {
-        Ok(_) => {
-            io.within(req.lobby_id.to_string())
-                .emit(ServerEvent::AnswerSubmitted, &req.card_id)
-                .await
-                .ok();
-
-            if !winners.is_empty() {
-                io.within(req.lobby_id.to_string())
-                    .emit(ServerEvent::WinnerDetected, &winners)
-                    .await
-                    .ok();
-            }
+    match lobby.submit_answer(req.card_id) {
+        Ok(true) => {}
+        // Repeated submissions succeed without changing anything
+        Ok(false) => {
+            let _ = ack.send(&Acknowledgement::success(AnswerSubmitAck {
+                already_submitted: true,
+            }));
+            return;
         }
         Err(err) => {
-            error!("Failed to send submit board ack: {}", err);
+            error!("Socket {} failed to submit an answer: {}", socket.id, err);
+            let _ = ack.send(&Acknowledgement::rejected(&err));
+            return;
+        }
+    }
+
+    let new_placements = lobby.record_placements();
+
+    let host = lobby.host.name.clone();
+    lobby.history.record(
+        Some(&host),
+        GameEvent::AnswerSubmitted {
+            card_id: req.card_id,
+        },
+    );
+    if !new_placements.is_empty() {
+        lobby.history.record(
+            None,
+            GameEvent::WinnerDetected {
+                placements: new_placements.clone(),
+            },
+        );
+    }
+
+    // The game ends on its own once every place has been awarded
+    let completed = lobby.state == LobbyState::InProgress && lobby.placements_filled();
+    if completed {
+        lobby.complete();
+        lobby.history.record(
+            None,
+            GameEvent::StageAdvanced {
+                state: LobbyState::Completed,
+            },
+        );
+        info!(lobby_id = %req.lobby_id, "all places awarded, completing lobby");
+    }
+    lobby.mark_changed();
+
+    match ack.send(&Acknowledgement::success(AnswerSubmitAck {
+        already_submitted: false,
+    })) {
+        Ok(_) => {
+            broadcast::to_lobby(
+                &io,
+                req.lobby_id,
+                &mut lobby.events,
+                ServerEvent::AnswerSubmitted,
+                &req.card_id,
+            )
+            .await;
+
+            if !new_placements.is_empty() {
+                broadcast::to_lobby(
+                    &io,
+                    req.lobby_id,
+                    &mut lobby.events,
+                    ServerEvent::WinnerDetected,
+                    &new_placements,
+                )
+                .await;
+            }
+
+            if !completed {
+                emit_near_wins(&io, req.lobby_id, lobby).await;
+            }
+
+            if completed {
+                broadcast::to_lobby(
+                    &io,
+                    req.lobby_id,
+                    &mut lobby.events,
+                    ServerEvent::NextStage,
+                    &LobbyState::Completed,
+                )
+                .await;
+
+                stage::emit_game_completed(&io, req.lobby_id, lobby).await;
+                stage::emit_snapshots(&io, lobby).await;
+            }
+        }
+        Err(err) => {
+            error!("Failed to send submit answer ack: {}", err);
+        }
+    };
+}
+
+/// Tells the audience chosen in the lobby settings who is a single card away from winning.
+async fn emit_near_wins(io: &SocketIo, lobby_id: LobbyId, lobby: &mut Lobby) {
+    let near_wins = lobby.near_wins();
+    if near_wins.is_empty() {
+        return;
+    }
+
+    match lobby.settings.near_wins {
+        NearWinVisibility::Hidden => {}
+        NearWinVisibility::Host => {
+            let host = lobby.host.id;
+            broadcast::to_socket(
+                io,
+                host,
+                &mut lobby.events,
+                ServerEvent::NearWin,
+                &near_wins,
+            )
+            .await;
+        }
+        NearWinVisibility::Everyone => {
+            broadcast::to_lobby(
+                io,
+                lobby_id,
+                &mut lobby.events,
+                ServerEvent::NearWin,
+                &near_wins,
+            )
+            .await;
+        }
+    }
+}
+
+#[instrument(name = "lobby.retract_answer", skip(socket, io, manager, ack))]
+pub async fn retract_answer(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(req): Data<AnswerRetractRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if !lobby.is_host(socket.id) {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotHost,
+            "Only the host can retract answers",
+        ));
+        return;
+    }
+
+    // A win that ended the game can still be taken back, which reopens it
+    let was_completed = lobby.state == LobbyState::Completed;
+
+    let revoked = match lobby.retract_answer(req.card_id) {
+        Ok(revoked) => revoked,
+        Err(err) => {
+            let _ = ack.send(&Acknowledgement::rejected(&err));
+            return;
+        }
+    };
+
+    info!(
+        lobby_id = %req.lobby_id,
+        card_id = req.card_id,
+        ?revoked,
+        "retracted answer"
+    );
+
+    let host = lobby.host.name.clone();
+    lobby.history.record(
+        Some(&host),
+        GameEvent::AnswerRetracted {
+            card_id: req.card_id,
+        },
+    );
+    lobby.mark_changed();
+
+    match ack.send(&Acknowledgement::success(())) {
+        Ok(_) => {
+            broadcast::to_lobby(
+                &io,
+                req.lobby_id,
+                &mut lobby.events,
+                ServerEvent::AnswerRetracted,
+                &AnswerRetracted {
+                    card_id: req.card_id,
+                    revoked,
+                    placements: &lobby.placements,
+                },
+            )
+            .await;
+
+            if was_completed {
+                if lobby.state == LobbyState::InProgress {
+                    broadcast::to_lobby(
+                        &io,
+                        req.lobby_id,
+                        &mut lobby.events,
+                        ServerEvent::NextStage,
+                        &LobbyState::InProgress,
+                    )
+                    .await;
+                } else {
+                    // Someone else took the freed place right away, the standings changed
+                    stage::emit_game_completed(&io, req.lobby_id, lobby).await;
+                }
+
+                stage::emit_snapshots(&io, lobby).await;
+            }
+        }
+        Err(err) => {
+            error!("Failed to send retract answer ack: {}", err);
+        }
+    };
+}
+
+#[instrument(
+    name = "lobby.resume_host",
+    skip(socket, io, request, manager, ack),
+    fields(lobby_id = %request.lobby_id)
+)]
+async fn resume_host(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(request): Data<ResumeHostRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&request.lobby_id) else {
+        error!("Lobby {} does not exist", request.lobby_id);
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if let Err(err) = lobby.resume_host(socket.id, &request.token) {
+        error!(
+            "Socket {} failed to resume hosting lobby {}: {}",
+            socket.id, request.lobby_id, err
+        );
+        let _ = ack.send(&Acknowledgement::rejected(&err));
+        return;
+    }
+    lobby.mark_changed();
+
+    let room = request.lobby_id.to_string();
+    socket.join(room.clone());
+
+    match ack.send(&Acknowledgement::success(LobbySnapshot::new(
+        lobby,
+        socket.id,
+        Role::Host,
+    ))) {
+        Ok(_) => {
+            info!(lobby_id = %request.lobby_id, "host reconnected");
+            broadcast::to_lobby(
+                &io,
+                request.lobby_id,
+                &mut lobby.events,
+                ServerEvent::HostReconnected,
+                &lobby.host.name,
+            )
+            .await;
+        }
+        Err(err) => error!("Failed to send resume host ack: {}", err),
+    }
+}
+
+#[instrument(
+    name = "lobby.resume_session",
+    skip(socket, io, request, manager, ack),
+    fields(lobby_id = %request.lobby_id)
+)]
+async fn resume_session(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(request): Data<ResumeSessionRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let token = request.token.or_else(|| {
+        CookieJar::from_headers(&socket.req_parts().headers)
+            .get(&resume_cookie(request.lobby_id))
+            .map(|cookie| ResumeToken::from(cookie.value()))
+    });
+
+    let Some(token) = token else {
+        error!("Socket {} did not send a resume token", socket.id);
+        let _ = ack.send(&Acknowledgement::rejected(&InvalidResumeToken));
+        return;
+    };
+
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&request.lobby_id) else {
+        error!("Lobby {} does not exist", request.lobby_id);
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if lobby.banned_tokens.contains(&token) {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::Banned,
+            "You are banned from this lobby",
+        ));
+        return;
+    }
+
+    let name = match lobby.resume_player(socket.id, &token) {
+        Ok(player) => player.name.clone(),
+        Err(err) => {
+            error!(
+                "Socket {} failed to resume a session in lobby {}: {}",
+                socket.id, request.lobby_id, err
+            );
+            let _ = ack.send(&Acknowledgement::rejected(&err));
+            return;
+        }
+    };
+    lobby.mark_changed();
+
+    let room = request.lobby_id.to_string();
+    socket.join(room.clone());
+
+    match ack.send(&Acknowledgement::success(LobbySnapshot::new(
+        lobby,
+        socket.id,
+        Role::Player,
+    ))) {
+        Ok(_) => {
+            info!(lobby_id = %request.lobby_id, player = %name, "player reconnected");
+            broadcast::to_lobby(
+                &io,
+                request.lobby_id,
+                &mut lobby.events,
+                ServerEvent::PlayerReconnected,
+                &name,
+            )
+            .await;
+        }
+        Err(err) => error!("Failed to send resume session ack: {}", err),
+    }
+}
+
+#[instrument(name = "lobby.get_state", skip(socket, manager, ack))]
+async fn get_lobby_state(
+    socket: SocketRef,
+    Data(lobby_id): Data<LobbyId>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get(&lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    let Some(role) = lobby.role_of(socket.id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotAPlayer,
+            "You are not part of this lobby",
+        ));
+        return;
+    };
+
+    if let Err(err) = ack.send(&Acknowledgement::success(LobbySnapshot::new(
+        lobby, socket.id, role,
+    ))) {
+        error!("Failed to send lobby state ack: {}", err);
+    }
+}
+
+#[instrument(name = "lobby.replay_events", skip(socket, manager, ack))]
+async fn replay_events(
+    socket: SocketRef,
+    Data(req): Data<ReplayEventsRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if lobby.role_of(socket.id).is_none() {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotAPlayer,
+            "You are not part of this lobby",
+        ));
+        return;
+    }
+
+    let (events, complete) = lobby.events.since(req.after, socket.id);
+
+    if let Err(err) = ack.send(&Acknowledgement::success(ReplayedEvents {
+        events,
+        complete,
+    })) {
+        error!("Failed to send replayed events ack: {}", err);
+    }
+}
+
+#[instrument(name = "lobby.transfer_host", skip(socket, io, manager, ack))]
+async fn transfer_host(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(req): Data<TransferHostRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if !lobby.is_host(socket.id) {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotHost,
+            "Only the host can hand over the lobby",
+        ));
+        return;
+    }
+
+    let Some(player_id) = lobby.player_id_by_name(&req.player_name) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotAPlayer,
+            "Player does not exist",
+        ));
+        return;
+    };
+
+    let previous = lobby.host.name.clone();
+
+    if let Err(err) = lobby.transfer_host(player_id, true) {
+        error!("Failed to make {} the host: {}", req.player_name, err);
+        let _ = ack.send(&Acknowledgement::rejected(&err));
+        return;
+    }
+
+    info!(lobby_id = %req.lobby_id, host = %req.player_name, "transferred host");
+    lobby.history.record(
+        Some(&previous),
+        GameEvent::HostTransferred {
+            name: req.player_name.clone(),
+            keep_previous: true,
+        },
+    );
+    lobby.mark_changed();
+
+    match ack.send(&Acknowledgement::success(())) {
+        Ok(_) => stage::emit_host_changed(&io, req.lobby_id, lobby, previous).await,
+        Err(err) => error!("Failed to send transfer host ack: {}", err),
+    }
+}
+
+#[instrument(name = "lobby.export_history", skip(socket, manager, ack))]
+async fn export_history(
+    socket: SocketRef,
+    Data(lobby_id): Data<LobbyId>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get(&lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if lobby.role_of(socket.id).is_none() {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotAPlayer,
+            "You are not part of this lobby",
+        ));
+        return;
+    }
+
+    // The history would spoil a game that is still running
+    if lobby.state != LobbyState::Completed {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::WrongStage,
+            "The game is not finished yet",
+        ));
+        return;
+    }
+
+    if let Err(err) = ack.send(&Acknowledgement::success(lobby.history.entries())) {
+        error!("Failed to send history ack: {}", err);
+    }
+}
+
+#[instrument(name = "lobby.replay_game", skip(socket, manager, ack))]
+async fn replay_game(
+    socket: SocketRef,
+    Data(req): Data<ReplayGameRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+) {
+    let lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    let Some(role) = lobby.role_of(socket.id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotAPlayer,
+            "You are not part of this lobby",
+        ));
+        return;
+    };
+
+    if lobby.state != LobbyState::Completed {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::WrongStage,
+            "The game is not finished yet",
+        ));
+        return;
+    }
+
+    let entries = lobby.history.entries();
+    let Some(entry) = entries.get(req.step) else {
+        let _ = ack.send(&Acknowledgement::rejected(&ReplayError { step: req.step }));
+        return;
+    };
+
+    let replayed = match lobby.history.replay(req.step + 1) {
+        Ok(replayed) => replayed,
+        Err(err) => {
+            error!(lobby_id = %req.lobby_id, "Failed to replay history: {}", err);
+            let _ = ack.send(&Acknowledgement::rejected(&err));
+            return;
+        }
+    };
+
+    // The replayed lobby has made up sockets, so boards are matched by name instead
+    let mut snapshot = LobbySnapshot::new(&replayed, socket.id, role);
+    snapshot.boards = Some(replayed.boards_by_name());
+
+    if let Err(err) = ack.send(&Acknowledgement::success(ReplayStep {
+        step: req.step,
+        steps: entries.len(),
+        entry,
+        lobby: snapshot,
+    })) {
+        error!("Failed to send replay step ack: {}", err);
+    }
+}
+
+#[instrument(name = "lobby.kick_player", skip(socket, io, manager, ack))]
+async fn kick_player(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(req): Data<KickPlayerRequest>,
+    State(manager): State<LobbyManager>,
+    ack: AckSender,
+    ban: bool,
+) {
+    let mut lobbies = manager.lock().await;
+
+    let Some(lobby) = lobbies.get_mut(&req.lobby_id) else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::LobbyNotFound,
+            "Lobby does not exist",
+        ));
+        return;
+    };
+
+    if !lobby.is_host(socket.id) {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotHost,
+            "Only the host can remove players",
+        ));
+        return;
+    }
+
+    let Some(player) = lobby
+        .player_id_by_name(&req.player_name)
+        .and_then(|player_id| lobby.kick_player(&player_id, ban))
+    else {
+        let _ = ack.send(&Acknowledgement::failure(
+            ErrorCode::NotAPlayer,
+            "Player does not exist",
+        ));
+        return;
+    };
+
+    info!(lobby_id = %req.lobby_id, player = %player.name, ban, "kicked player");
+
+    let host = lobby.host.name.clone();
+    lobby.history.record(
+        Some(&host),
+        GameEvent::Kicked {
+            name: player.name.clone(),
+            banned: ban,
+        },
+    );
+    lobby.mark_changed();
+
+    match ack.send(&Acknowledgement::success(())) {
+        Ok(_) => {
+            // The player is told before they are taken out of the room
+            broadcast::to_lobby(
+                &io,
+                req.lobby_id,
+                &mut lobby.events,
+                ServerEvent::PlayerKicked,
+                &PlayerKicked {
+                    name: player.name,
+                    banned: ban,
+                },
+            )
+            .await;
+
+            if let Some(kicked) = io.get_socket(player.id) {
+                kicked.leave(req.lobby_id.to_string());
+            }
+
+            waitlist::promote(&io, req.lobby_id, lobby).await;
+
+            if lobby.settings.teams {
+                let teams = lobby.team_summaries();
+                broadcast::to_lobby(
+                    &io,
+                    req.lobby_id,
+                    &mut lobby.events,
+                    ServerEvent::TeamsChanged,
+                    &teams,
+                )
+                .await;
+            }
+        }
+        Err(err) => {
+            error!("Failed to send kick player ack: {}", err);
         }
     };
 }
@@ -1,31 +1,164 @@
 use serde::Deserialize;
 
-use crate::model::LobbyId;
+use crate::model::{
+    BoardSeed,
+    CardId,
+    LobbyId,
+    LobbySettings,
+    ResumeToken,
+    Secret,
+};
+
+/// What lets a socket into a private lobby, either one will do.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LobbyAccess {
+    pub password: Option<Secret>,
+    pub invite: Option<Secret>,
+}
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JoinLobbyRequest {
     pub player_name: String,
     pub lobby_id: LobbyId,
+    #[serde(flatten)]
+    pub access: LobbyAccess,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectateLobbyRequest {
+    pub lobby_id: LobbyId,
+    #[serde(flatten)]
+    pub access: LobbyAccess,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct HostLobbyRequest {
     pub host_name: String,
-    pub cards: [String; 25],
+    #[serde(flatten)]
+    pub settings: LobbySettings,
+    pub cards: Vec<String>,
+
+    /// Makes the lobby private, players then need it to join.
+    #[serde(default)]
+    pub password: Option<Secret>,
+
+    /// Makes the lobby private with a generated invite secret, alone or next to the password.
+    #[serde(default)]
+    pub invite: bool,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BoardSubmitRequest {
     pub lobby_id: LobbyId,
-    pub cards: [u8; 25],
+    pub cards: Vec<CardId>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AnswerSubmitRequest {
     pub lobby_id: LobbyId,
-    pub card_id: u8,
+    pub card_id: CardId,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnswerRetractRequest {
+    pub lobby_id: LobbyId,
+    pub card_id: CardId,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateBoardRequest {
+    pub lobby_id: LobbyId,
+
+    /// Reproduces a shared board, a random one is generated without it.
+    #[serde(default)]
+    pub seed: Option<BoardSeed>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinTeamRequest {
+    pub lobby_id: LobbyId,
+
+    /// Leaves the current team when missing.
+    #[serde(default)]
+    pub team: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssignTeamRequest {
+    pub lobby_id: LobbyId,
+    pub player_name: String,
+
+    /// Removes the player from their team when missing.
+    #[serde(default)]
+    pub team: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewRoundRequest {
+    pub lobby_id: LobbyId,
+
+    /// Replaces the card pool, the current one is kept when missing.
+    #[serde(default)]
+    pub cards: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumeHostRequest {
+    pub lobby_id: LobbyId,
+    pub token: ResumeToken,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumeSessionRequest {
+    pub lobby_id: LobbyId,
+
+    /// Falls back to the lobby's resume token cookie if missing.
+    #[serde(default)]
+    pub token: Option<ResumeToken>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayEventsRequest {
+    pub lobby_id: LobbyId,
+
+    /// The sequence number of the last event the client received.
+    pub after: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferHostRequest {
+    pub lobby_id: LobbyId,
+    pub player_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayGameRequest {
+    pub lobby_id: LobbyId,
+
+    /// The index of the history entry to replay up to, including it.
+    pub step: usize,
+}
+
+/// Used by both `kickPlayer` and `banPlayer`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KickPlayerRequest {
+    pub lobby_id: LobbyId,
+    pub player_name: String,
 }
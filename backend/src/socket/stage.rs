@@ -0,0 +1,231 @@
+use std::time::Duration;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use socketioxide::SocketIo;
+use tracing::info;
+
+use crate::{
+    history::GameEvent,
+    model::{
+        LastStateReached,
+        Lobby,
+        LobbyId,
+        LobbyManager,
+        LobbyState,
+    },
+    socket::{
+        acks::{
+            HostChanged,
+            LobbySnapshot,
+            StageDeadline,
+            Standings,
+        },
+        broadcast,
+        events::ServerEvent,
+    },
+};
+
+/// Seconds before a deadline at which clients are reminded of it.
+const DEADLINE_REMINDERS: [i64; 2] = [60, 10];
+
+/// Moves the lobby to its next stage and tells everyone in it. Starts the timer of the new stage,
+/// if it has a deadline. `actor` is who advanced it, `None` when a deadline ran out.
+pub async fn advance_stage(
+    io: &SocketIo,
+    manager: &LobbyManager,
+    lobby_id: LobbyId,
+    lobby: &mut Lobby,
+    actor: Option<String>,
+) -> Result<LobbyState, LastStateReached> {
+    let previous = lobby.state;
+    let state = lobby.advance_state()?;
+    lobby
+        .history
+        .record(actor.as_deref(), GameEvent::StageAdvanced { state });
+    lobby.mark_changed();
+
+    // Players without a team were just put into one
+    if previous == LobbyState::WaitingForPlayers && lobby.settings.teams {
+        let teams = lobby.team_summaries();
+        broadcast::to_lobby(
+            io,
+            lobby_id,
+            &mut lobby.events,
+            ServerEvent::TeamsChanged,
+            &teams,
+        )
+        .await;
+    }
+
+    // Players who didn't craft a board, or weren't meant to, get one from the server
+    if state == LobbyState::InProgress {
+        for (sid, board) in lobby.assign_random_boards() {
+            if let Some(player) = lobby.players.get(&sid) {
+                let name = player.name.clone();
+                lobby.history.record(
+                    None,
+                    GameEvent::BoardAssigned {
+                        name,
+                        cards: board.cards.clone(),
+                    },
+                );
+            }
+
+            broadcast::to_socket(
+                io,
+                sid,
+                &mut lobby.events,
+                ServerEvent::BoardAssigned,
+                &board,
+            )
+            .await;
+        }
+    }
+
+    broadcast::to_lobby(
+        io,
+        lobby_id,
+        &mut lobby.events,
+        ServerEvent::NextStage,
+        &state,
+    )
+    .await;
+
+    if state == LobbyState::Completed {
+        emit_game_completed(io, lobby_id, lobby).await;
+    }
+
+    emit_snapshots(io, lobby).await;
+
+    if let Some(deadline) = lobby.deadline {
+        emit_deadline(io, lobby_id, lobby, deadline).await;
+        schedule_deadline(io.clone(), manager.clone(), lobby_id, deadline);
+    }
+
+    Ok(state)
+}
+
+pub async fn emit_game_completed(io: &SocketIo, lobby_id: LobbyId, lobby: &mut Lobby) {
+    broadcast::to_lobby(
+        io,
+        lobby_id,
+        &mut lobby.events,
+        ServerEvent::GameCompleted,
+        &Standings {
+            round: lobby.round,
+            placements: &lobby.placements,
+            scores: &lobby.scores,
+        },
+    )
+    .await;
+}
+
+async fn emit_deadline(
+    io: &SocketIo,
+    lobby_id: LobbyId,
+    lobby: &mut Lobby,
+    deadline: DateTime<Utc>,
+) {
+    broadcast::to_lobby(
+        io,
+        lobby_id,
+        &mut lobby.events,
+        ServerEvent::StageDeadline,
+        &StageDeadline {
+            state: lobby.state,
+            deadline,
+            remaining_seconds: (deadline - Utc::now()).num_seconds().max(0),
+        },
+    )
+    .await;
+}
+
+/// Reminds the lobby of the deadline and advances it once the deadline passes. Does nothing if
+/// the lobby left the stage in the meantime.
+pub fn schedule_deadline(
+    io: SocketIo,
+    manager: LobbyManager,
+    lobby_id: LobbyId,
+    deadline: DateTime<Utc>,
+) {
+    tokio::spawn(async move {
+        let checkpoints = DEADLINE_REMINDERS
+            .into_iter()
+            .map(|seconds| deadline - chrono::Duration::seconds(seconds))
+            .filter(|checkpoint| *checkpoint > Utc::now())
+            .chain([deadline]);
+
+        for checkpoint in checkpoints {
+            let delay = (checkpoint - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+            tokio::time::sleep(delay).await;
+
+            let mut lobbies = manager.lock().await;
+
+            let Some(lobby) = lobbies.get_mut(&lobby_id) else {
+                return;
+            };
+
+            if lobby.deadline != Some(deadline) {
+                return;
+            }
+
+            if checkpoint < deadline {
+                emit_deadline(&io, lobby_id, lobby, deadline).await;
+                continue;
+            }
+
+            info!(%lobby_id, state = ?lobby.state, "stage deadline passed");
+            advance_stage(&io, &manager, lobby_id, lobby, None)
+                .await
+                .ok();
+        }
+    });
+}
+
+/// Tells the lobby who its new host is, and everyone their new view of it.
+pub async fn emit_host_changed(
+    io: &SocketIo,
+    lobby_id: LobbyId,
+    lobby: &mut Lobby,
+    previous: String,
+) {
+    let host = lobby.host.name.clone();
+    broadcast::to_lobby(
+        io,
+        lobby_id,
+        &mut lobby.events,
+        ServerEvent::HostChanged,
+        &HostChanged {
+            host: &host,
+            previous,
+        },
+    )
+    .await;
+
+    emit_snapshots(io, lobby).await;
+}
+
+/// Sends everyone in the lobby their own view of it. Snapshots aren't logged, they carry the
+/// sequence number of the latest event instead.
+pub async fn emit_snapshots(io: &SocketIo, lobby: &Lobby) {
+    let sockets = std::iter::once(lobby.host.id)
+        .chain(lobby.players.keys().copied())
+        .chain(lobby.spectators.iter().copied());
+
+    for sid in sockets {
+        let Some(role) = lobby.role_of(sid) else {
+            continue;
+        };
+
+        io.to(sid)
+            .emit(
+                ServerEvent::LobbySnapshot,
+                &LobbySnapshot::new(lobby, sid, role),
+            )
+            .await
+            .ok();
+    }
+}
@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use socketioxide::SocketIo;
+use tracing::info;
+
+use crate::{
+    config::Config,
+    history::GameEvent,
+    model::{
+        CloseReason,
+        Lobby,
+        LobbyId,
+        LobbyManager,
+        ResumeToken,
+    },
+    socket::{
+        acks::LobbyClosed,
+        broadcast,
+        events::ServerEvent,
+        stage,
+        waitlist,
+    },
+};
+
+/// Periodically closes lobbies that were abandoned or completed a while ago.
+pub async fn run(io: SocketIo, manager: LobbyManager, config: Config) {
+    let idle_ttl =
+        chrono::Duration::from_std(config.idle_lobby_ttl).unwrap_or(chrono::Duration::MAX);
+    let completed_ttl =
+        chrono::Duration::from_std(config.completed_lobby_ttl).unwrap_or(chrono::Duration::MAX);
+
+    let mut interval = tokio::time::interval(config.sweep_interval);
+
+    loop {
+        interval.tick().await;
+
+        let now = Utc::now();
+        let mut lobbies = manager.lock().await;
+
+        let expired: Vec<_> = lobbies
+            .iter()
+            .filter_map(|(lobby_id, lobby)| {
+                lobby
+                    .expiry(now, idle_ttl, completed_ttl)
+                    .map(|reason| (*lobby_id, reason))
+            })
+            .collect();
+
+        for (lobby_id, reason) in expired {
+            let Some(mut lobby) = lobbies.remove(&lobby_id) else {
+                continue;
+            };
+            info!(%lobby_id, %reason, lobby_count = lobbies.len(), "evicted lobby");
+
+            close_room(&io, lobby_id, &mut lobby, reason).await;
+        }
+    }
+}
+
+/// Gives everyone in the lobbies loaded on startup a chance to reconnect, and restarts the timers
+/// that were running before the restart.
+pub async fn resume_loaded_lobbies(io: &SocketIo, manager: &LobbyManager, config: &Config) {
+    let mut lobbies = manager.lock().await;
+    let lobby_ids: Vec<LobbyId> = lobbies.keys().copied().collect();
+
+    for lobby_id in lobby_ids {
+        let Some(lobby) = lobbies.get_mut(&lobby_id) else {
+            continue;
+        };
+
+        let disconnected_at = lobby.disconnect_everyone();
+        lobby.mark_changed();
+
+        schedule_host_timeout(
+            io.clone(),
+            manager.clone(),
+            lobby_id,
+            disconnected_at,
+            config.host_grace_period,
+        );
+
+        for player in lobby.players.values() {
+            schedule_player_timeout(
+                io.clone(),
+                manager.clone(),
+                lobby_id,
+                player.token.clone(),
+                disconnected_at,
+                config.player_grace_period,
+            );
+        }
+
+        if let Some(deadline) = lobby.deadline {
+            stage::schedule_deadline(io.clone(), manager.clone(), lobby_id, deadline);
+        }
+    }
+
+    info!(lobby_count = lobbies.len(), "resumed loaded lobbies");
+}
+
+/// Hands the lobby to a player, or closes it, if its host is still gone once the grace period is
+/// over.
+pub fn schedule_host_timeout(
+    io: SocketIo,
+    manager: LobbyManager,
+    lobby_id: LobbyId,
+    disconnected_at: DateTime<Utc>,
+    grace_period: Duration,
+) {
+    tokio::spawn(async move {
+        tokio::time::sleep(grace_period).await;
+
+        let mut lobbies = manager.lock().await;
+
+        // The host came back in the meantime
+        let Some(lobby) = lobbies.get_mut(&lobby_id) else {
+            return;
+        };
+        if lobby.host_disconnected_at != Some(disconnected_at) {
+            return;
+        }
+
+        if lobby.settings.promote_host
+            && let Some(candidate) = lobby.promotion_candidate()
+        {
+            let previous = lobby.host.name.clone();
+            if lobby.transfer_host(candidate, false).is_ok() {
+                let host = lobby.host.name.clone();
+                lobby.history.record(
+                    None,
+                    GameEvent::HostTransferred {
+                        name: host,
+                        keep_previous: false,
+                    },
+                );
+                lobby.mark_changed();
+                info!(%lobby_id, host = %lobby.host.name, "host did not come back, promoted a player");
+                stage::emit_host_changed(&io, lobby_id, lobby, previous).await;
+
+                // The promoted player's seat is free now
+                waitlist::promote(&io, lobby_id, lobby).await;
+                return;
+            }
+        }
+
+        let Some(mut lobby) = lobbies.remove(&lobby_id) else {
+            return;
+        };
+        info!(%lobby_id, lobby_count = lobbies.len(), "host did not come back, deleted lobby");
+
+        close_room(&io, lobby_id, &mut lobby, CloseReason::HostLeft).await;
+    });
+}
+
+/// Gives up the seat of the player holding `token` if they are still gone once the grace period is
+/// over.
+pub fn schedule_player_timeout(
+    io: SocketIo,
+    manager: LobbyManager,
+    lobby_id: LobbyId,
+    token: ResumeToken,
+    disconnected_at: DateTime<Utc>,
+    grace_period: Duration,
+) {
+    tokio::spawn(async move {
+        tokio::time::sleep(grace_period).await;
+
+        let mut lobbies = manager.lock().await;
+        let Some(lobby) = lobbies.get_mut(&lobby_id) else {
+            return;
+        };
+
+        // The player came back in the meantime
+        let Some(sid) = lobby
+            .players
+            .values()
+            .find(|player| player.token == token && player.disconnected_at == Some(disconnected_at))
+            .map(|player| player.id)
+        else {
+            return;
+        };
+
+        let Some(player) = lobby.remove_player(&sid) else {
+            return;
+        };
+        info!(%lobby_id, player = %player.name, "player did not come back, removed them");
+        lobby.history.record(
+            None,
+            GameEvent::Left {
+                name: player.name.clone(),
+            },
+        );
+        lobby.mark_changed();
+
+        broadcast::to_lobby(
+            &io,
+            lobby_id,
+            &mut lobby.events,
+            ServerEvent::UserLeft,
+            &player.name,
+        )
+        .await;
+
+        waitlist::promote(&io, lobby_id, lobby).await;
+
+        if lobby.settings.teams {
+            let teams = lobby.team_summaries();
+            broadcast::to_lobby(
+                &io,
+                lobby_id,
+                &mut lobby.events,
+                ServerEvent::TeamsChanged,
+                &teams,
+            )
+            .await;
+        }
+    });
+}
+
+/// Tells everyone in the lobby's room that it was closed and empties the room.
+async fn close_room(io: &SocketIo, lobby_id: LobbyId, lobby: &mut Lobby, reason: CloseReason) {
+    broadcast::to_lobby(
+        io,
+        lobby_id,
+        &mut lobby.events,
+        ServerEvent::LobbyClosed,
+        &LobbyClosed { reason },
+    )
+    .await;
+
+    let room = lobby_id.to_string();
+    io.within(room.clone()).leave(room).await.ok();
+}
@@ -0,0 +1,72 @@
+use std::{
+    collections::VecDeque,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// How many wrong passwords a socket may send within [`FAILED_JOIN_WINDOW`].
+const MAX_FAILED_JOINS: usize = 5;
+const FAILED_JOIN_WINDOW: Duration = Duration::from_secs(60);
+
+/// The recent wrong passwords of a socket, kept in its extensions.
+#[derive(Debug, Clone, Default)]
+pub struct FailedJoins(VecDeque<Instant>);
+
+impl FailedJoins {
+    /// How long the socket has to wait before it may try again, if it failed too often.
+    pub fn retry_after(&mut self, now: Instant) -> Option<Duration> {
+        while self
+            .0
+            .front()
+            .is_some_and(|failed_at| now.duration_since(*failed_at) >= FAILED_JOIN_WINDOW)
+        {
+            self.0.pop_front();
+        }
+
+        if self.0.len() < MAX_FAILED_JOINS {
+            return None;
+        }
+
+        self.0
+            .front()
+            .map(|oldest| FAILED_JOIN_WINDOW - now.duration_since(*oldest))
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        self.0.push_back(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{
+        Duration,
+        Instant,
+    };
+
+    use super::{
+        FAILED_JOIN_WINDOW,
+        FailedJoins,
+        MAX_FAILED_JOINS,
+    };
+
+    #[test]
+    fn sockets_wait_after_too_many_failures() {
+        let start = Instant::now();
+        let mut failed = FailedJoins::default();
+
+        for _ in 0..MAX_FAILED_JOINS {
+            assert_eq!(failed.retry_after(start), None);
+            failed.record(start);
+        }
+
+        let later = start + Duration::from_secs(20);
+        assert_eq!(
+            failed.retry_after(later),
+            Some(FAILED_JOIN_WINDOW - Duration::from_secs(20))
+        );
+        assert_eq!(failed.retry_after(start + FAILED_JOIN_WINDOW), None);
+    }
+}
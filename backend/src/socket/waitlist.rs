@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+
+use socketioxide::SocketIo;
+use tracing::info;
+
+use crate::{
+    history::GameEvent,
+    model::{
+        Lobby,
+        LobbyId,
+        LobbyState,
+        Player,
+    },
+    socket::{
+        acks::JoinLobbyAck,
+        broadcast,
+        events::ServerEvent,
+    },
+};
+
+/// The lobbies whose waitlist a socket joined, kept in its extensions. Waiting sockets aren't in
+/// the lobby's room yet, so this is how they are found when they disconnect.
+#[derive(Debug, Clone, Default)]
+pub struct Waitlisted(pub HashSet<LobbyId>);
+
+/// Seats sockets from the waitlist while the lobby has room and is still waiting for players.
+/// Sockets that disconnected while waiting are dropped.
+pub async fn promote(io: &SocketIo, lobby_id: LobbyId, lobby: &mut Lobby) {
+    while lobby.state == LobbyState::WaitingForPlayers && !lobby.is_full() {
+        let Some(entry) = lobby.waitlist.pop_front() else {
+            return;
+        };
+        let Some(socket) = io.get_socket(entry.id) else {
+            continue;
+        };
+
+        let player = Player::new(entry.id, entry.name.clone());
+        let resume_token = player.token.clone();
+        lobby.add_player(player);
+        lobby.history.record(
+            Some(&entry.name),
+            GameEvent::Joined {
+                name: entry.name.clone(),
+            },
+        );
+        lobby.mark_changed();
+        info!(%lobby_id, player = %entry.name, "seated a player from the waitlist");
+
+        socket.join(lobby_id.to_string());
+
+        let joined = JoinLobbyAck::new(lobby, resume_token);
+        broadcast::to_socket(
+            io,
+            entry.id,
+            &mut lobby.events,
+            ServerEvent::PromotedFromWaitlist,
+            &joined,
+        )
+        .await;
+
+        broadcast::to_lobby(
+            io,
+            lobby_id,
+            &mut lobby.events,
+            ServerEvent::UserJoined,
+            &entry.name,
+        )
+        .await;
+    }
+}
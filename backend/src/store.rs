@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io,
+    iter,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        Arc,
+        mpsc,
+    },
+    thread,
+};
+
+use tracing::{
+    error,
+    warn,
+};
+
+use crate::model::{
+    Lobby,
+    LobbyId,
+};
+
+/// Keeps lobbies around across server restarts.
+pub trait LobbyStore: Send + Sync {
+    /// Every lobby that was saved and not removed since.
+    fn load(&self) -> io::Result<HashMap<LobbyId, Lobby>>;
+
+    fn save(&self, lobby_id: LobbyId, lobby: &Lobby) -> io::Result<()>;
+
+    fn remove(&self, lobby_id: LobbyId) -> io::Result<()>;
+}
+
+#[derive(Debug)]
+enum StoreOp {
+    Save(LobbyId, Box<Lobby>),
+    Remove(LobbyId),
+}
+
+/// Hands lobbies to a store on a thread of its own, so a slow disk never holds up the runtime.
+#[derive(Debug, Clone)]
+pub struct StoreWriter {
+    ops: mpsc::Sender<StoreOp>,
+}
+
+impl StoreWriter {
+    pub fn spawn(store: Arc<dyn LobbyStore>) -> io::Result<Self> {
+        let (ops, pending) = mpsc::channel();
+        thread::Builder::new()
+            .name("lobby-store".to_string())
+            .spawn(move || write_all(store.as_ref(), pending))?;
+
+        Ok(Self { ops })
+    }
+
+    pub fn save(&self, lobby_id: LobbyId, lobby: Lobby) {
+        self.send(StoreOp::Save(lobby_id, Box::new(lobby)));
+    }
+
+    pub fn remove(&self, lobby_id: LobbyId) {
+        self.send(StoreOp::Remove(lobby_id));
+    }
+
+    fn send(&self, op: StoreOp) {
+        if self.ops.send(op).is_err() {
+            error!("The lobby store writer has stopped, changes are no longer saved");
+        }
+    }
+}
+
+fn write_all(store: &dyn LobbyStore, pending: mpsc::Receiver<StoreOp>) {
+    while let Ok(op) = pending.recv() {
+        // Only the latest state of each lobby is worth writing
+        let mut latest = HashMap::new();
+        for op in iter::once(op).chain(pending.try_iter()) {
+            match op {
+                StoreOp::Save(lobby_id, lobby) => latest.insert(lobby_id, Some(lobby)),
+                StoreOp::Remove(lobby_id) => latest.insert(lobby_id, None),
+            };
+        }
+
+        for (lobby_id, lobby) in latest {
+            let result = match lobby {
+                Some(lobby) => store.save(lobby_id, &lobby),
+                None => store.remove(lobby_id),
+            };
+
+            if let Err(err) = result {
+                error!("Failed to persist lobby {}: {}", lobby_id, err);
+            }
+        }
+    }
+}
+
+/// Keeps every lobby as a JSON file in a directory.
+///
+/// The files hold everything about a lobby, including its password, invite secret and resume
+/// tokens in plaintext, so the directory should only be readable by the server.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, lobby_id: LobbyId) -> PathBuf {
+        self.dir.join(format!("{lobby_id}.json"))
+    }
+}
+
+impl LobbyStore for FileStore {
+    fn load(&self) -> io::Result<HashMap<LobbyId, Lobby>> {
+        let mut lobbies = HashMap::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+
+            let Some(lobby_id) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|name| LobbyId::from_str(name).ok())
+            else {
+                continue;
+            };
+
+            // A broken file shouldn't keep the other lobbies from loading
+            match serde_json::from_slice(&fs::read(&path)?) {
+                Ok(lobby) => {
+                    lobbies.insert(lobby_id, lobby);
+                }
+                Err(err) => warn!("Skipping unreadable lobby file {}: {}", path.display(), err),
+            }
+        }
+
+        Ok(lobbies)
+    }
+
+    fn save(&self, lobby_id: LobbyId, lobby: &Lobby) -> io::Result<()> {
+        let path = self.path(lobby_id);
+        let temporary = path.with_extension("json.tmp");
+
+        // Renaming is atomic, so a crash never leaves a half written lobby behind
+        fs::write(&temporary, serde_json::to_vec(lobby)?)?;
+        fs::rename(temporary, path)
+    }
+
+    fn remove(&self, lobby_id: LobbyId) -> io::Result<()> {
+        match fs::remove_file(self.path(lobby_id)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use socketioxide::socket::Sid;
+
+    use super::*;
+    use crate::model::{
+        Host,
+        LobbySettings,
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wfb-store-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn sample_lobby(host: &str) -> Lobby {
+        Lobby::new(
+            Host::new(Sid::new(), host.into()),
+            LobbySettings::default(),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn saved_lobbies_load_back() {
+        let dir = test_dir("load");
+        let store = FileStore::new(&dir).unwrap();
+        let lobby_id = LobbyId::new();
+        let lobby = sample_lobby("host");
+
+        store.save(lobby_id, &lobby).unwrap();
+        let loaded = store.load().unwrap();
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[&lobby_id].host.name, "host");
+        assert_eq!(loaded[&lobby_id].host_token, lobby.host_token);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn removed_lobbies_are_gone() {
+        let dir = test_dir("remove");
+        let store = FileStore::new(&dir).unwrap();
+        let lobby_id = LobbyId::new();
+
+        store.save(lobby_id, &sample_lobby("host")).unwrap();
+        store.remove(lobby_id).unwrap();
+
+        assert!(store.load().unwrap().is_empty());
+        // Removing twice is fine
+        store.remove(lobby_id).unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn broken_files_are_skipped() {
+        let dir = test_dir("broken");
+        let store = FileStore::new(&dir).unwrap();
+        let lobby_id = LobbyId::new();
+
+        store.save(lobby_id, &sample_lobby("host")).unwrap();
+        fs::write(store.path(LobbyId::new()), "{ not a lobby").unwrap();
+        fs::write(dir.join("notes.txt"), "not a lobby either").unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert!(loaded.contains_key(&lobby_id));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
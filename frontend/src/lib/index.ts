@@ -71,7 +71,8 @@ export const COLOR_THEMES = [
 ];
 
 export const socket: Socket<ServerToClientEvents, ClientToServerEvents> =
-    io('http://localhost:3000/');
+    // Credentials let the handshake carry the resume cookies to the backend
+    io('http://localhost:3000/', { withCredentials: true });
 
 socket.on('disconnect', (reason, details) => {
     // the reason of the disconnection, for example "transport error"
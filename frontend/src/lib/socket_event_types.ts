@@ -2,23 +2,170 @@
 
 import type { Card, LobbyState, ServerCard } from '$lib';
 
+export type ErrorCode =
+    | 'lobbyNotFound'
+    | 'notHost'
+    | 'notAPlayer'
+    | 'wrongStage'
+    | 'nameTaken'
+    | 'invalidSettings'
+    | 'invalidCardPool'
+    | 'invalidBoard'
+    | 'unknownCard'
+    | 'duplicateCard'
+    | 'answerNotSubmitted'
+    | 'teamsDisabled'
+    | 'invalidTeamName'
+    | 'invalidResumeToken'
+    | 'playerDisconnected'
+    | 'replayFailed'
+    | 'alreadyInLobby'
+    | 'banned'
+    | 'wrongPassword'
+    | 'tooManyAttempts'
+    | 'lobbyFull'
+    | 'seatsAvailable';
+
 type Acknowledgement<T> =
     | {
           success: true;
           data: T;
       }
-    | { success: false; error: string };
+    | { success: false; code: ErrorCode; message: string };
+
+/** Side length of a square board, defaults to 5 */
+export type BoardSize = 3 | 4 | 5 | 6 | 7;
+
+export type WinPattern =
+    | { kind: 'anyLine' }
+    | { kind: 'twoLines' }
+    | { kind: 'fourCorners' }
+    | { kind: 'xShape' }
+    | { kind: 'plus' }
+    | { kind: 'frame' }
+    | { kind: 'blackout' }
+    /** Bit `n` marks cell `n`, counted row by row from the top-left cell */
+    | { kind: 'custom'; mask: number };
+
+export interface Winner {
+    /** The team's name in team mode */
+    name: string;
+    /** Only present in team mode */
+    members?: string[];
+    pattern: WinPattern;
+    /** Board cells that completed the pattern */
+    cells: number[];
+}
+
+export interface Placement extends Winner {
+    /** 1-based, players winning on the same answer share a place */
+    place: number;
+    answerIndex: number;
+    wonAt: string;
+}
+
+export interface LobbySettings {
+    boardSize: BoardSize;
+    winPattern: WinPattern;
+    /** Number of places awarded before the game ends on its own, defaults to 1 */
+    places: number;
+    /** Reserves the center as an always marked free space, boards then hold one card less */
+    freeCenter: boolean;
+    /** Who is told about players one card away from winning, defaults to everyone */
+    nearWins: 'hidden' | 'host' | 'everyone';
+    /** The server hands out random boards and the crafting stage is skipped */
+    autoBoards: boolean;
+    /** Players form teams that share a board and win together */
+    teams: boolean;
+    /** Seconds until a stage ends on its own */
+    deadlines: { crafting?: number; game?: number };
+    /** A player takes over when the host doesn't come back in time */
+    promoteHost: boolean;
+    /** Spectators see every player's board once the game is in progress */
+    spectatorBoards: boolean;
+    /** The most players the lobby seats, capped by the server. Further ones can join the waitlist */
+    maxPlayers: number;
+}
+
+export interface Team {
+    name: string;
+    members: string[];
+}
+
+export interface Score {
+    name: string;
+    /** The last place awarded in a round is worth one point, every place above one more */
+    points: number;
+    wins: number;
+}
+
+export interface Standings {
+    round: number;
+    placements: Placement[];
+    /** Cumulative over every round played in the lobby */
+    scores: Score[];
+}
+
+export interface NewRoundRequest {
+    lobbyId: string;
+    /** Replaces the card pool, the current one is kept when missing */
+    cards?: string[];
+}
+
+export interface JoinTeamRequest {
+    lobbyId: string;
+    /** Leaves the current team when missing */
+    team?: string;
+}
+
+export interface AssignTeamRequest {
+    lobbyId: string;
+    playerName: string;
+    team?: string;
+}
+
+export interface GeneratedBoard {
+    /** Share it to let others reproduce the board */
+    seed: number;
+    cards: number[];
+}
 
-export interface HostLobbyRequest {
+export interface GenerateBoardRequest {
+    lobbyId: string;
+    seed?: number;
+}
+
+export interface NearWin {
+    name: string;
+    /** Each of these cards would complete the win pattern on its own */
+    cards: number[];
+}
+
+export interface HostLobbyRequest extends Partial<LobbySettings> {
     hostName: string;
+    /** Card pool players pick their boards from, at least one card per board cell and at most 200 */
     cards: string[];
+    /** Makes the lobby private, players then need it to join */
+    password?: string;
+    /** Makes the lobby private with a generated invite secret, alone or next to the password */
+    invite?: boolean;
+}
+
+/** What lets a socket into a private lobby, either one will do */
+export interface LobbyAccess {
+    password?: string;
+    invite?: string;
 }
 
-export interface JoinLobbyRequest {
+export interface JoinLobbyRequest extends LobbyAccess {
     lobbyId: string;
     playerName: string;
 }
 
+export interface SpectateLobbyRequest extends LobbyAccess {
+    lobbyId: string;
+}
+
 export interface SubmitBoardRequest {
     lobbyId: string;
     cards: number[];
@@ -29,23 +176,159 @@ export interface SubmitAnswerRequest {
     cards: number;
 }
 
-// --- ACKNOWLEDGMENT PAYLOADS (Server -> Client) ---
+export interface RetractAnswerRequest {
+    lobbyId: string;
+    cardId: number;
+}
 
-export type HostLobbyAck = Acknowledgement<{
+export interface TransferHostRequest {
     lobbyId: string;
-    cards: ServerCard[];
-}>;
+    playerName: string;
+}
+
+/** Used by both `kickPlayer` and `banPlayer` */
+export interface KickPlayerRequest {
+    lobbyId: string;
+    playerName: string;
+}
+
+export interface ReplayEventsRequest {
+    lobbyId: string;
+    /** The sequence number of the last event received */
+    after: number;
+}
+
+export interface ReplayGameRequest {
+    lobbyId: string;
+    /** The index of the history entry to replay up to, including it */
+    step: number;
+}
 
-export type JoinLobbyAck = Acknowledgement<{
+export interface ResumeSessionRequest {
+    lobbyId: string;
+    /** Falls back to the `wfb_resume_token_<lobbyId>` cookie if missing */
+    token?: string;
+}
+
+export interface ResumeHostRequest {
+    lobbyId: string;
+    token: string;
+}
+
+// --- ACKNOWLEDGMENT PAYLOADS (Server -> Client) ---
+
+export type HostLobbyAck = Acknowledgement<
+    LobbySettings & {
+        lobbyId: string;
+        /** Send with `resumeHost` to take the lobby back after reconnecting */
+        resumeToken: string;
+        /** Only if the host asked for one, to be shared with the players */
+        inviteSecret?: string;
+        cards: ServerCard[];
+    }
+>;
+
+export type JoinLobbyAck = Acknowledgement<
+    LobbySettings & {
+        /** Send with `resumeSession` to take the seat back after reconnecting */
+        resumeToken: string;
+        host: string;
+        cards: ServerCard[];
+        players: string[];
+        teams: Team[];
+    }
+>;
+
+export type LobbySnapshot = LobbySettings & {
+    /** The snapshot includes every event up to this sequence number */
+    seq: number;
+    state: LobbyState;
+    round: number;
     host: string;
     cards: ServerCard[];
     players: string[];
+    teams: Team[];
+    correctAnswers: number[];
+    placements: Placement[];
+    /** The names of the players who have a board */
+    submittedBoards: string[];
+    role: Role;
+    /** The caller's own board, if they have one yet */
+    board: number[] | null;
+    /** Empty unless the lobby's settings let the caller see near wins */
+    nearWins: NearWin[];
+    /** Every player's board by name, for spectators once the game is in progress if allowed */
+    boards?: Record<string, number[]>;
+};
+
+export type Role = 'host' | 'player' | 'spectator';
+
+export type WaitlistAck = Acknowledgement<{
+    /** 1-based, the first socket gets the next free seat */
+    position: number;
+}>;
+
+export type LobbySnapshotAck = Acknowledgement<LobbySnapshot>;
+
+/** Sent as the last argument of every server event except `lobbySnapshot` */
+export interface EventMeta {
+    seq: number;
+    timestamp: string;
+}
+
+export interface LoggedEvent extends EventMeta {
+    event: keyof ServerToClientEvents;
+    data: unknown;
+}
+
+export type ReplayEventsAck = Acknowledgement<{
+    events: LoggedEvent[];
+    /** False if some missed events are gone, use `getLobbyState` instead */
+    complete: boolean;
 }>;
 
+export type GameEvent =
+    | { kind: 'created'; host: string; settings: LobbySettings; cards: ServerCard[] }
+    | { kind: 'joined'; name: string }
+    | { kind: 'left'; name: string }
+    | { kind: 'kicked'; name: string; banned: boolean }
+    | { kind: 'teamChanged'; name: string; team: string | null }
+    | { kind: 'boardSubmitted'; name: string; cards: number[] }
+    | { kind: 'boardAssigned'; name: string; cards: number[] }
+    | { kind: 'stageAdvanced'; state: LobbyState }
+    | { kind: 'answerSubmitted'; cardId: number }
+    | { kind: 'answerRetracted'; cardId: number }
+    | { kind: 'winnerDetected'; placements: Placement[] }
+    | { kind: 'newRound'; cards: ServerCard[] }
+    | { kind: 'hostTransferred'; name: string; keepPrevious: boolean };
+
+export type HistoryEntry = GameEvent & {
+    at: string;
+    /** Who caused the event, null for the server itself */
+    actor: string | null;
+};
+
+export type ExportHistoryAck = Acknowledgement<HistoryEntry[]>;
+
+export type ReplayGameAck = Acknowledgement<
+    LobbySnapshot & {
+        step: number;
+        /** How many entries the history has in total */
+        steps: number;
+        entry: HistoryEntry;
+        /** Every board handed out so far, by the name of the player */
+        boards: Record<string, number[]>;
+    }
+>;
+
 export type NextStageAck = Acknowledgement<string>;
 
 export type EmptyAck = Acknowledgement<null>;
 
+export type GenerateBoardAck = Acknowledgement<GeneratedBoard>;
+
+export type SubmitAnswerAck = Acknowledgement<{ alreadySubmitted: boolean }>;
+
 export interface ClientToServerEvents {
     // Event: 'hostLobby'
     // Arguments: [HostLobbyRequest, (ack: HostLobbyAck) => void]
@@ -55,20 +338,86 @@ export interface ClientToServerEvents {
     // Arguments: [JoinLobbyRequest, (ack: JoinLobbyAck) => void]
     joinLobby: (data: JoinLobbyRequest, callback: (ack: JoinLobbyAck) => void) => void;
 
+    /** Only for full lobbies, `promotedFromWaitlist` follows once a seat frees up */
+    joinWaitlist: (data: JoinLobbyRequest, callback: (ack: WaitlistAck) => void) => void;
+
+    /** Joins in any stage to watch, without taking part. Banned players get `banned` */
+    spectateLobby: (data: SpectateLobbyRequest, callback: (ack: LobbySnapshotAck) => void) => void;
+
     triggerNextStage: (data: string) => void;
 
     submitBoard: (data: SubmitBoardRequest, callback: (ack: EmptyAck) => void) => void;
 
-    submitAnswer: (data: SubmitAnswerRequest, callback: (ack: EmptyAck) => void) => void;
+    submitAnswer: (data: SubmitAnswerRequest, callback: (ack: SubmitAnswerAck) => void) => void;
+
+    /** In a completed game only if it costs someone their place, `nextStage` then reopens the game */
+    retractAnswer: (data: RetractAnswerRequest, callback: (ack: EmptyAck) => void) => void;
+
+    generateBoard: (data: GenerateBoardRequest, callback: (ack: GenerateBoardAck) => void) => void;
+
+    joinTeam: (data: JoinTeamRequest, callback: (ack: EmptyAck) => void) => void;
+
+    assignTeam: (data: AssignTeamRequest, callback: (ack: EmptyAck) => void) => void;
+
+    startNewRound: (data: NewRoundRequest, callback: (ack: EmptyAck) => void) => void;
+
+    resumeHost: (data: ResumeHostRequest, callback: (ack: LobbySnapshotAck) => void) => void;
+
+    getLobbyState: (lobbyId: string, callback: (ack: LobbySnapshotAck) => void) => void;
+
+    transferHost: (data: TransferHostRequest, callback: (ack: EmptyAck) => void) => void;
+
+    /** Only removes the player, who may join or spectate again */
+    kickPlayer: (data: KickPlayerRequest, callback: (ack: EmptyAck) => void) => void;
+
+    /** Also keeps the player's name, resume token and socket from joining or spectating again */
+    banPlayer: (data: KickPlayerRequest, callback: (ack: EmptyAck) => void) => void;
+
+    replayEvents: (data: ReplayEventsRequest, callback: (ack: ReplayEventsAck) => void) => void;
+
+    /** Only once the game is completed */
+    exportHistory: (lobbyId: string, callback: (ack: ExportHistoryAck) => void) => void;
+
+    replayGame: (data: ReplayGameRequest, callback: (ack: ReplayGameAck) => void) => void;
+
+    resumeSession: (
+        data: ResumeSessionRequest,
+        callback: (ack: LobbySnapshotAck) => void,
+    ) => void;
 }
 
+/** Every listener also receives an `EventMeta` after the listed arguments */
 export interface ServerToClientEvents {
     // Server pushes this event to notify clients a new user joined
     userJoined: (userName: string) => void;
 
     userLeft: (userName: string) => void;
 
-    lobbyClosed: () => void;
+    lobbyClosed: (event: { reason: 'idle' | 'completed' | 'hostLeft' }) => void;
+
+    /** The lobby closes unless the host reconnects within `graceSeconds` */
+    hostDisconnected: (event: { graceSeconds: number }) => void;
+
+    hostReconnected: (hostName: string) => void;
+
+    /** The player keeps their seat for a while, `userLeft` follows if they don't come back */
+    playerDisconnected: (userName: string) => void;
+
+    playerReconnected: (userName: string) => void;
+
+    /** Sent to a waitlisted socket that just got a seat, like the `joinLobby` ack */
+    promotedFromWaitlist: (
+        joined: Extract<JoinLobbyAck, { success: true }>['data'],
+    ) => void;
+
+    /** The kicked player receives it too, right before being removed from the lobby */
+    playerKicked: (event: { name: string; banned: boolean }) => void;
+
+    /** The previous host stays as a player, unless they left for good */
+    hostChanged: (event: { host: string; previous: string }) => void;
+
+    /** Sent to everyone in the lobby whenever the stage or the host changes */
+    lobbySnapshot: (snapshot: LobbySnapshot) => void;
 
     nextStage: (state: LobbyState) => void;
 
@@ -76,5 +425,32 @@ export interface ServerToClientEvents {
 
     answerSubmitted: (cardId: number) => void;
 
-    winnerDetected: (winners: string[]) => void;
+    /** Only contains the placements awarded by the latest answer */
+    winnerDetected: (placements: Placement[]) => void;
+
+    gameCompleted: (standings: Standings) => void;
+
+    /** The lobby went back to waiting for players, boards and answers are cleared */
+    newRound: (round: { round: number; cards: ServerCard[]; scores: Score[] }) => void;
+
+    nearWin: (nearWins: NearWin[]) => void;
+
+    /** Sent to a player whose board was generated by the server */
+    boardAssigned: (board: GeneratedBoard) => void;
+
+    teamsChanged: (teams: Team[]) => void;
+
+    /** Sent when a timed stage starts and shortly before it ends */
+    stageDeadline: (deadline: {
+        state: LobbyState;
+        deadline: string;
+        remainingSeconds: number;
+    }) => void;
+
+    /** `placements` replaces every placement known so far */
+    answerRetracted: (event: {
+        cardId: number;
+        revoked: string[];
+        placements: Placement[];
+    }) => void;
 }